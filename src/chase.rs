@@ -0,0 +1,561 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::dependencies::{field_closure, FD};
+use crate::model::{Field, Schema, Table};
+use crate::symbols::{FieldName, TableName};
+
+/// The result of checking whether a table of the original schema can be
+/// rebuilt by joining tables of a normalized schema
+pub struct JoinCheck {
+    /// The name of the original table
+    pub table: TableName,
+
+    /// The tables which are joined along INDs to rebuild the table
+    pub tables: Vec<TableName>,
+
+    /// Fields of the original table which the join does not recover
+    pub missing_fields: Vec<FieldName>,
+
+    /// Tables holding missing fields which no IND joins with the others
+    pub unjoined: Vec<(TableName, Vec<FieldName>)>,
+}
+
+impl JoinCheck {
+    /// Check if the table is rebuilt by the join without losing rows
+    pub fn is_lossless(&self) -> bool {
+        self.missing_fields.is_empty()
+    }
+}
+
+impl fmt::Display for JoinCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_lossless() {
+            return write!(
+                f,
+                "{}: lossless join of {}",
+                self.table,
+                self.tables.iter().join(", ")
+            );
+        }
+
+        if self.tables.is_empty() {
+            write!(f, "{}: no tables hold its fields", self.table)?;
+        } else {
+            write!(
+                f,
+                "{}: not a lossless join of {} (missing {})",
+                self.table,
+                self.tables.iter().join(", "),
+                self.missing_fields.iter().join(", ")
+            )?;
+        }
+        for (table, fields) in &self.unjoined {
+            write!(
+                f,
+                "\n  no IND joins {} which has {}",
+                table,
+                fields.iter().join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An FD of an original table which is not implied by the FDs of the
+/// normalized tables holding its fields
+pub struct LostFD {
+    /// The name of the original table
+    pub table: TableName,
+
+    /// The FD which is no longer implied
+    pub fd: FD,
+
+    /// Fields on the RHS of the FD which are not determined by the LHS
+    pub missing_fields: Vec<FieldName>,
+
+    /// The normalized tables holding fields of the FD
+    pub tables: Vec<TableName>,
+}
+
+impl fmt::Display for LostFD {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.table,
+            self.fd.lhs.iter().sorted().join(", "),
+            self.fd.rhs.iter().sorted().join(", ")
+        )?;
+        if !self.fd.is_exact() {
+            write!(f, " @{}", self.fd.confidence)?;
+        }
+
+        write!(
+            f,
+            ": {} not determined within {}",
+            self.missing_fields.iter().join(", "),
+            self.tables.iter().join(", ")
+        )
+    }
+}
+
+/// Map the fields of a normalized table to the fields of an original
+/// table which they hold the values of, either because the normalized
+/// table was derived from the original table or because an IND in the
+/// original schema shows the values appear in another table
+fn field_mapping(
+    original: &Schema,
+    table: &Table,
+    normalized: &Table,
+) -> HashMap<FieldName, FieldName> {
+    let sources = normalized.source_names();
+    let mut aliases: HashMap<(TableName, FieldName), FieldName> = HashMap::new();
+    for ind in original.inds.values().flatten() {
+        if ind.left_table == table.name && sources.contains(&ind.right_table) {
+            for (left, right) in ind.left_fields.iter().zip(ind.right_fields.iter()) {
+                aliases
+                    .entry((ind.right_table.clone(), right.clone()))
+                    .or_insert_with(|| left.clone());
+            }
+        }
+    }
+
+    let mut mapping = HashMap::new();
+    for field in normalized.fields.keys() {
+        if sources.contains(&table.name) && table.fields.contains_key(field) {
+            mapping.insert(field.clone(), field.clone());
+            continue;
+        }
+
+        let alias = sources
+            .iter()
+            .filter_map(|source| aliases.get(&(source.clone(), field.clone())))
+            .next();
+        if let Some(alias) = alias {
+            mapping.insert(field.clone(), alias.clone());
+        }
+    }
+
+    mapping
+}
+
+/// Find the tables of a normalized schema which hold the values of any
+/// field of an original table along with the mapping of their fields
+fn holders<'a>(
+    original: &Schema,
+    table: &Table,
+    normalized: &'a Schema,
+) -> Vec<(&'a Table, HashMap<FieldName, FieldName>)> {
+    normalized
+        .tables
+        .values()
+        .sorted_by_key(|t| t.name.clone())
+        .map(|t| (t, field_mapping(original, table, t)))
+        .filter(|(_, mapping)| !mapping.is_empty())
+        .collect()
+}
+
+/// Chase a tableau with a row for each set of fields using the exact FDs
+/// of a table, producing the fields which are recovered in the row
+/// recovering the most fields
+fn chase(table: &Table, rows: &[HashSet<FieldName>]) -> HashSet<FieldName> {
+    let fields = table.fields.keys().cloned().collect::<Vec<_>>();
+
+    // Symbol zero is distinguished and others are unique to a cell
+    let mut tableau = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            fields
+                .iter()
+                .enumerate()
+                .map(|(j, f)| {
+                    if row.contains(f) {
+                        0
+                    } else {
+                        i * fields.len() + j + 1
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let fds = table
+        .fds
+        .values()
+        .filter(|fd| fd.is_exact())
+        .map(|fd| {
+            let positions = |side: &HashSet<FieldName>| {
+                side.iter()
+                    .filter_map(|f| fields.iter().position(|f2| f2 == f))
+                    .collect::<Vec<_>>()
+            };
+            (positions(&fd.lhs), positions(&fd.rhs))
+        })
+        .collect::<Vec<_>>();
+
+    // Rows which agree on the LHS of an FD must agree on the RHS, so
+    // equate symbols until no FD changes the tableau
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (lhs, rhs) in &fds {
+            for i in 0..tableau.len() {
+                for j in (i + 1)..tableau.len() {
+                    if lhs.iter().any(|&c| tableau[i][c] != tableau[j][c]) {
+                        continue;
+                    }
+
+                    for &c in rhs {
+                        let (keep, replace) = if tableau[i][c] < tableau[j][c] {
+                            (tableau[i][c], tableau[j][c])
+                        } else {
+                            (tableau[j][c], tableau[i][c])
+                        };
+                        if keep == replace {
+                            continue;
+                        }
+
+                        for row in tableau.iter_mut() {
+                            if row[c] == replace {
+                                row[c] = keep;
+                            }
+                        }
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    tableau
+        .iter()
+        .map(|row| {
+            fields
+                .iter()
+                .zip(row.iter())
+                .filter(|&(_, &symbol)| symbol == 0)
+                .map(|(f, _)| f.clone())
+                .collect::<HashSet<_>>()
+        })
+        .max_by_key(|recovered| recovered.len())
+        .unwrap_or_default()
+}
+
+/// Check that a table can be rebuilt from a normalized schema by joining
+/// the tables holding its fields along INDs
+fn check_table(original: &Schema, table: &Table, normalized: &Schema) -> JoinCheck {
+    let holders = holders(original, table, normalized)
+        .into_iter()
+        .map(|(t, mapping)| (t.name.clone(), mapping))
+        .collect::<Vec<_>>();
+
+    // Group the tables which are connected by INDs since only those can
+    // be joined to rebuild the table
+    let names = holders
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<HashSet<_>>();
+    let mut components: Vec<HashSet<TableName>> = Vec::new();
+    for name in &names {
+        components.push(Some(name.clone()).into_iter().collect());
+    }
+    for ind in normalized.inds.values().flatten() {
+        if !names.contains(&ind.left_table) || !names.contains(&ind.right_table) {
+            continue;
+        }
+        let left = components
+            .iter()
+            .position(|c| c.contains(&ind.left_table))
+            .unwrap();
+        let right = components
+            .iter()
+            .position(|c| c.contains(&ind.right_table))
+            .unwrap();
+        if left != right {
+            let merged = components.remove(usize::max(left, right));
+            components[usize::min(left, right)].extend(merged);
+        }
+    }
+
+    // Use the group of tables which recovers the most fields, preferring
+    // the first group by name when several recover the same number
+    let best = components
+        .iter()
+        .map(|component| {
+            let rows = holders
+                .iter()
+                .filter(|(name, _)| component.contains(name))
+                .map(|(_, mapping)| mapping.values().cloned().collect::<HashSet<_>>())
+                .collect::<Vec<_>>();
+            (component, chase(table, &rows))
+        })
+        .max_by_key(|(component, recovered)| {
+            (
+                recovered.len(),
+                Reverse(component.iter().sorted().collect::<Vec<_>>()),
+            )
+        });
+    let (tables, recovered) = match best {
+        Some((component, recovered)) => (component.iter().cloned().sorted().collect(), recovered),
+        None => (Vec::new(), HashSet::new()),
+    };
+
+    let missing_fields = table
+        .fields
+        .keys()
+        .filter(|f| !recovered.contains(*f))
+        .cloned()
+        .collect::<Vec<_>>();
+    let unjoined = holders
+        .iter()
+        .filter(|(name, _)| !tables.contains(name))
+        .map(|(name, mapping)| {
+            let fields = missing_fields
+                .iter()
+                .filter(|f| mapping.values().any(|v| v == *f))
+                .cloned()
+                .collect::<Vec<_>>();
+            (name.clone(), fields)
+        })
+        .filter(|(_, fields)| !fields.is_empty())
+        .collect();
+
+    JoinCheck {
+        table: table.name.clone(),
+        tables,
+        missing_fields,
+        unjoined,
+    }
+}
+
+/// Check that every table of an original schema can be rebuilt without
+/// losing rows by joining the tables of a normalized schema along INDs
+pub fn check_lossless(original: &Schema, normalized: &Schema) -> Vec<JoinCheck> {
+    original
+        .tables
+        .values()
+        .sorted_by_key(|t| t.name.clone())
+        .map(|table| check_table(original, table, normalized))
+        .collect()
+}
+
+/// Find the FDs of every original table which are not implied by the
+/// union of its FDs projected onto the fields held by each normalized
+/// table, considering the FDs of a canonical cover of each table
+///
+/// The FDs declared on normalized tables are not used since a key chosen
+/// during decomposition may not be implied by the original FDs.
+pub fn lost_fds(original: &Schema, normalized: &Schema) -> Vec<LostFD> {
+    let mut lost = Vec::new();
+    for table in original.tables.values().sorted_by_key(|t| t.name.clone()) {
+        let holders = holders(original, table, normalized);
+
+        // Project the FDs of the original table onto the original fields
+        // held by each table
+        let fds = holders
+            .iter()
+            .flat_map(|(t, mapping)| {
+                let fields = mapping.values().collect::<HashSet<_>>();
+                let mut projected = Table {
+                    name: t.name.clone(),
+                    fields: table
+                        .fields
+                        .values()
+                        .filter(|f| fields.contains(&f.name))
+                        .map(|f| {
+                            (
+                                f.name.clone(),
+                                Field {
+                                    key: false,
+                                    ..f.clone()
+                                },
+                            )
+                        })
+                        .collect(),
+                    ..Default::default()
+                };
+                projected.copy_fds(table);
+                projected.all_fds().cloned().collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        for fd in table.canonical_cover() {
+            let closure = field_closure(&fds, &fd.lhs);
+            let missing_fields = fd
+                .rhs
+                .iter()
+                .filter(|f| closure.get(*f).is_none_or(|&c| c < fd.confidence))
+                .cloned()
+                .sorted()
+                .collect::<Vec<_>>();
+            if missing_fields.is_empty() {
+                continue;
+            }
+
+            let tables = holders
+                .iter()
+                .filter(|(_, mapping)| {
+                    mapping
+                        .values()
+                        .any(|f| fd.lhs.contains(f) || fd.rhs.contains(f))
+                })
+                .map(|(t, _)| t.name.clone())
+                .collect();
+            lost.push(LostFD {
+                table: table.name.clone(),
+                fd,
+                missing_fields,
+                tables,
+            });
+        }
+    }
+
+    lost
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dependencies::IND;
+    use crate::normalize::Normalizer;
+
+    #[test]
+    fn check_normalized() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("foo", true),
+              field!("bar"),
+              field!("baz")
+            }
+        );
+        t.add_fd(field_vec!["bar"], field_vec!["baz"]);
+        let original = schema! {t};
+
+        let mut normalized = original.clone();
+        let normalizer = Normalizer {
+            use_stats: false,
+            fd_threshold: None,
+            min_confidence: None,
+        };
+        normalizer.normalize(&mut normalized);
+
+        let checks = check_lossless(&original, &normalized);
+        assert_eq!(checks.len(), 1);
+        assert!(checks[0].is_lossless());
+        assert_eq!(
+            checks[0].to_string(),
+            "foo: lossless join of foo_base, foo_ext"
+        );
+        assert!(lost_fds(&original, &normalized).is_empty());
+    }
+
+    #[test]
+    fn check_subsumed() {
+        let mut t1 = table!(
+            "foo",
+            fields! {
+              field!("foo", true),
+              field!("bar")
+            }
+        );
+        let mut t2 = table!(
+            "qux",
+            fields! {
+              field!("quux", true),
+              field!("corge")
+            }
+        );
+        t2.add_fd(field_vec!["quux"], field_vec!["corge"]);
+        let mut original = schema! {t1, t2};
+        add_ind!(original, "foo", ["foo", "bar"], "qux", ["quux", "corge"]);
+
+        // The values of bar can be found from corge after subsumption
+        let mut normalized = original.clone();
+        t1 = table!("foo", fields! { field!("foo", true) });
+        normalized.tables.insert(t1.name.clone(), t1);
+        normalized.inds.clear();
+        add_ind!(normalized, "foo", ["foo"], "qux", ["quux"]);
+
+        let checks = check_lossless(&original, &normalized);
+        assert!(checks[0].is_lossless(), "{}", checks[0]);
+        assert!(checks[1].is_lossless(), "{}", checks[1]);
+    }
+
+    #[test]
+    fn lost_bcnf_fd() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("foo", true),
+              field!("bar", true),
+              field!("baz")
+            }
+        );
+        t.add_fd(field_vec!["foo", "bar"], field_vec!["baz"]);
+        t.add_fd(field_vec!["baz"], field_vec!["bar"]);
+        let original = schema! {t};
+
+        // Splitting on baz -> bar leaves no table with foo and bar
+        let mut normalized = original.clone();
+        let normalizer = Normalizer {
+            use_stats: false,
+            fd_threshold: None,
+            min_confidence: None,
+        };
+        normalizer.normalize(&mut normalized);
+
+        let lost = lost_fds(&original, &normalized);
+        assert_eq!(lost.len(), 1);
+        assert_eq!(
+            lost[0].to_string(),
+            "foo: bar, foo -> baz: baz not determined within foo_base, foo_ext"
+        );
+    }
+
+    #[test]
+    fn check_missing_join() {
+        let t = table!(
+            "foo",
+            fields! {
+              field!("foo", true),
+              field!("bar"),
+              field!("baz")
+            }
+        );
+        let original = schema! {t};
+
+        // Without an FD, splitting the table loses the association
+        let mut t1 = table!(
+            "foo_base",
+            fields! {
+              field!("foo", true),
+              field!("bar")
+            }
+        );
+        let mut t2 = table!(
+            "foo_ext",
+            fields! {
+              field!("bar", true),
+              field!("baz")
+            }
+        );
+        t1.sources = vec![TableName::from("foo")];
+        t2.sources = vec![TableName::from("foo")];
+        let normalized = schema! {t1, t2};
+
+        let checks = check_lossless(&original, &normalized);
+        assert!(!checks[0].is_lossless());
+        assert_eq!(
+            checks[0].to_string(),
+            "foo: not a lossless join of foo_base (missing baz)\n  \
+             no IND joins foo_ext which has baz"
+        );
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use indexmap::IndexMap;
+use itertools::Itertools;
+
+use crate::data::Relation;
+use crate::dependencies::{FD, IND};
+use crate::input::Input;
+use crate::symbols::FieldName;
+
+/// The largest number of counter-rows reported for each dependency
+const MAX_EXAMPLES: usize = 3;
+
+/// The result of testing a dependency against data
+pub struct Check {
+    /// The dependency written in the input format
+    pub dependency: String,
+
+    /// The number of rows the dependency was tested on
+    pub rows: usize,
+
+    /// The number of distinct values on the left-hand side which
+    /// violate the dependency
+    pub violations: usize,
+
+    /// The fraction of rows which would need to be removed for the
+    /// dependency to hold (the g3 measure)
+    pub error: f64,
+
+    /// The fraction of rows the dependency is declared to hold on
+    pub confidence: f32,
+
+    /// Descriptions of rows which violate the dependency
+    pub examples: Vec<String>,
+}
+
+impl Check {
+    /// Check if the dependency holds on as many rows as declared
+    pub fn holds(&self) -> bool {
+        self.violations == 0
+            || (self.confidence < 1.0 && 1.0 - self.error as f32 >= self.confidence)
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.violations == 0 {
+            return write!(f, "{}: holds on {} rows", self.dependency, self.rows);
+        }
+
+        write!(
+            f,
+            "{}: {} violation{} on {} rows (g3 = {:.4})",
+            self.dependency,
+            self.violations,
+            if self.violations == 1 { "" } else { "s" },
+            self.rows,
+            self.error
+        )?;
+        if self.holds() {
+            write!(f, " within confidence")?;
+        }
+        for example in &self.examples {
+            write!(f, "\n  {}", example)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Find the index of the column for each field in a relation
+fn column_indexes(relation: &Relation, fields: &[FieldName]) -> Result<Vec<usize>, String> {
+    fields
+        .iter()
+        .map(|field| {
+            relation
+                .columns
+                .iter()
+                .position(|c| c == field.as_ref())
+                .ok_or_else(|| format!("no column {} in data for {}", field, relation.name))
+        })
+        .collect()
+}
+
+/// Produce the values of some columns in a row unless any are empty
+fn row_values<'a>(row: &'a [String], columns: &[usize]) -> Option<Vec<&'a str>> {
+    let values = columns.iter().map(|&c| row[c].as_str()).collect::<Vec<_>>();
+    if values.iter().any(|v| v.is_empty()) {
+        None
+    } else {
+        Some(values)
+    }
+}
+
+/// Describe a row by the values of some of its columns
+fn row_text(relation: &Relation, row: usize, columns: &[usize]) -> String {
+    let values = columns
+        .iter()
+        .map(|&c| format!("{} = {}", relation.columns[c], relation.rows[row][c]))
+        .join(", ");
+    format!("row {}: {}", row + 1, values)
+}
+
+/// Test an FD on the rows of a relation, ignoring rows where any
+/// value on the left-hand side is empty
+pub fn check_fd(relation: &Relation, fd: &FD) -> Result<Check, String> {
+    let lhs = fd.lhs.iter().cloned().sorted().collect::<Vec<_>>();
+    let rhs = fd.rhs.iter().cloned().sorted().collect::<Vec<_>>();
+    let mut dependency = format!(
+        "{} {} -> {}",
+        relation.name,
+        lhs.iter().join(", "),
+        rhs.iter().join(", ")
+    );
+    if !fd.is_exact() {
+        dependency.push_str(&format!(" @{}", fd.confidence));
+    }
+    let lhs_columns =
+        column_indexes(relation, &lhs).map_err(|e| format!("{}: {}", dependency, e))?;
+    let rhs_columns =
+        column_indexes(relation, &rhs).map_err(|e| format!("{}: {}", dependency, e))?;
+
+    // Group rows by the left-hand side and then the right-hand side,
+    // keeping the first row and number of rows in each group
+    let mut rows = 0;
+    let mut groups = IndexMap::new();
+    for (i, row) in relation.rows.iter().enumerate() {
+        let lhs_values = match row_values(row, &lhs_columns) {
+            Some(values) => values,
+            None => continue,
+        };
+        rows += 1;
+
+        let rhs_values = rhs_columns
+            .iter()
+            .map(|&c| row[c].as_str())
+            .collect::<Vec<_>>();
+        groups
+            .entry(lhs_values)
+            .or_insert_with(IndexMap::new)
+            .entry(rhs_values)
+            .or_insert((i, 0))
+            .1 += 1;
+    }
+
+    // Only the most common right-hand side of each group can be kept
+    let conflicting = groups.values().filter(|g| g.len() > 1).collect::<Vec<_>>();
+    let removed = conflicting
+        .iter()
+        .map(|g| {
+            let counts = g.values().map(|&(_, count)| count);
+            counts.clone().sum::<usize>() - counts.max().unwrap()
+        })
+        .sum::<usize>();
+
+    let columns = lhs_columns
+        .iter()
+        .chain(rhs_columns.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+    let examples = conflicting
+        .first()
+        .map(|g| {
+            g.values()
+                .take(MAX_EXAMPLES)
+                .map(|&(row, _)| row_text(relation, row, &columns))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(Check {
+        dependency,
+        rows,
+        violations: conflicting.len(),
+        error: if rows == 0 {
+            0.0
+        } else {
+            removed as f64 / rows as f64
+        },
+        confidence: fd.confidence,
+        examples,
+    })
+}
+
+/// Test an IND on the rows of two relations, ignoring rows where any
+/// value of the included fields is empty
+pub fn check_ind(left: &Relation, right: &Relation, ind: &IND) -> Result<Check, String> {
+    let dependency = format!(
+        "{} {} <= {} {}",
+        ind.left_table,
+        ind.left_fields.iter().join(", "),
+        ind.right_table,
+        ind.right_fields.iter().join(", ")
+    );
+    let left_columns =
+        column_indexes(left, &ind.left_fields).map_err(|e| format!("{}: {}", dependency, e))?;
+    let right_columns =
+        column_indexes(right, &ind.right_fields).map_err(|e| format!("{}: {}", dependency, e))?;
+
+    let referenced = right
+        .rows
+        .iter()
+        .filter_map(|row| row_values(row, &right_columns))
+        .collect::<HashSet<_>>();
+
+    // Keep the first row and number of rows for each missing value
+    let mut rows = 0;
+    let mut missing = IndexMap::new();
+    for (i, row) in left.rows.iter().enumerate() {
+        let values = match row_values(row, &left_columns) {
+            Some(values) => values,
+            None => continue,
+        };
+        rows += 1;
+
+        if !referenced.contains(&values) {
+            missing.entry(values).or_insert((i, 0)).1 += 1;
+        }
+    }
+
+    let removed = missing.values().map(|&(_, count)| count).sum::<usize>();
+    let examples = missing
+        .values()
+        .take(MAX_EXAMPLES)
+        .map(|&(row, _)| row_text(left, row, &left_columns))
+        .collect();
+
+    Ok(Check {
+        dependency,
+        rows,
+        violations: missing.len(),
+        error: if rows == 0 {
+            0.0
+        } else {
+            removed as f64 / rows as f64
+        },
+        confidence: 1.0,
+        examples,
+    })
+}
+
+/// Test the key of each table and every FD and IND as they are declared
+/// in some input on the relations with the same names as its tables,
+/// skipping dependencies on tables without data
+pub fn check_input(input: &Input, relations: &[Relation]) -> Vec<Result<Check, String>> {
+    let relations = relations
+        .iter()
+        .map(|r| (r.name.as_str(), r))
+        .collect::<HashMap<_, _>>();
+    let mut checks = Vec::new();
+
+    let mut names = HashSet::new();
+    for table in input.tables.iter().sorted_by_key(|t| t.name.clone()) {
+        // Only the first definition of each table is used
+        if !names.insert(table.name.clone()) {
+            continue;
+        }
+        let relation = match relations.get(table.name.as_ref()) {
+            Some(relation) => relation,
+            None => {
+                info!("No data for {}", table.name);
+                continue;
+            }
+        };
+
+        // The FD from the primary key is the only one on a parsed table
+        for fd in table.fds.values() {
+            if !fd.is_trivial() {
+                checks.push(check_fd(relation, fd));
+            }
+        }
+
+        for (_, lhs, rhs, confidence) in input.fds.iter().filter(|fd| fd.0 == table.name.as_ref()) {
+            let fd = FD {
+                lhs: lhs.iter().map(|f| f.parse().unwrap()).collect(),
+                rhs: rhs.iter().map(|f| f.parse().unwrap()).collect(),
+                confidence: *confidence,
+            };
+            if !fd.is_trivial() {
+                checks.push(check_fd(relation, &fd));
+            }
+        }
+    }
+
+    let inds = input
+        .inds
+        .iter()
+        .map(|ind| IND {
+            left_table: ind.0.parse().unwrap(),
+            left_fields: ind.1.iter().map(|f| f.parse().unwrap()).collect(),
+            right_table: ind.2.parse().unwrap(),
+            right_fields: ind.3.iter().map(|f| f.parse().unwrap()).collect(),
+        })
+        .unique()
+        .sorted_by_key(|ind| ind.to_string());
+    for ind in inds {
+        let left = relations.get(ind.left_table.as_ref());
+        let right = relations.get(ind.right_table.as_ref());
+        if let (Some(left), Some(right)) = (left, right) {
+            checks.push(check_ind(left, right, &ind));
+        }
+    }
+
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+
+    fn relation(name: &str, columns: Vec<&str>, rows: Vec<Vec<&str>>) -> Relation {
+        Relation {
+            name: name.to_string(),
+            columns: columns.into_iter().map(|c| c.to_string()).collect(),
+            rows: rows
+                .into_iter()
+                .map(|r| r.into_iter().map(|v| v.to_string()).collect())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn check_violations() {
+        let parsed = input::parse(
+            "bids(*id, user_id, nickname)\nusers(*id)\n\n\
+             bids user_id -> nickname\n\nbids user_id <= users id\n",
+        )
+        .unwrap();
+        let relations = vec![
+            relation(
+                "bids",
+                vec!["id", "user_id", "nickname"],
+                vec![
+                    vec!["1", "1", "alice"],
+                    vec!["2", "1", "alice"],
+                    vec!["3", "1", "bob"],
+                    vec!["4", "2", "carol"],
+                    vec!["5", "", "dave"],
+                ],
+            ),
+            relation("users", vec!["id"], vec![vec!["1"], vec!["3"]]),
+        ];
+
+        let checks = check_input(&parsed, &relations)
+            .into_iter()
+            .map(|c| c.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(checks.len(), 3);
+
+        // The key holds
+        assert!(checks[0].holds());
+        assert_eq!(checks[0].rows, 5);
+
+        assert_eq!(checks[1].dependency, "bids user_id -> nickname");
+        assert_eq!(checks[1].violations, 1);
+        assert_eq!(checks[1].rows, 4);
+        assert_eq!(checks[1].error, 0.25);
+        assert_eq!(
+            checks[1].examples,
+            vec![
+                "row 1: user_id = 1, nickname = alice",
+                "row 3: user_id = 1, nickname = bob"
+            ]
+        );
+
+        assert_eq!(checks[2].dependency, "bids user_id <= users id");
+        assert_eq!(checks[2].violations, 1);
+        assert_eq!(checks[2].error, 0.25);
+        assert_eq!(checks[2].examples, vec!["row 4: user_id = 2"]);
+    }
+
+    #[test]
+    fn check_declared_fds() {
+        let parsed =
+            input::parse("users(*id, zip, city)\n\nusers zip -> city\nusers city -> zip\n")
+                .unwrap();
+        let relations = vec![relation(
+            "users",
+            vec!["id", "zip", "city"],
+            vec![vec!["1", "1", "a"], vec!["2", "1", "b"]],
+        )];
+
+        // Each declared FD is checked once without those it implies
+        let checks = check_input(&parsed, &relations)
+            .into_iter()
+            .map(|c| c.unwrap().dependency)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            checks,
+            vec![
+                "users id -> city, zip",
+                "users zip -> city",
+                "users city -> zip"
+            ]
+        );
+    }
+
+    #[test]
+    fn check_missing_column() {
+        let parsed = input::parse("users(*id, name)\n").unwrap();
+        let relations = vec![relation("users", vec!["id"], vec![vec!["1"]])];
+        let checks = check_input(&parsed, &relations);
+        assert_eq!(
+            checks[0].as_ref().err().unwrap(),
+            "users id -> name: no column name in data for users"
+        );
+    }
+}
@@ -0,0 +1,582 @@
+extern crate peg;
+
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+use itertools::Itertools;
+
+use crate::input::Input;
+use crate::model::{Field, FieldType, Schema, Table};
+use crate::sql::short_table_names;
+use crate::symbols::{FieldName, TableName};
+
+/// The maximum length of a table name in Cassandra
+const MAX_TABLE_NAME_LENGTH: usize = 48;
+
+/// A column or key definition inside a `CREATE TABLE` statement
+pub enum Element {
+    /// A column with its name, type, whether it is static and whether it is the primary key
+    Column(String, String, bool, bool),
+
+    /// A primary key given by the partition key and clustering columns
+    PrimaryKey(Vec<String>, Vec<String>),
+}
+
+peg::parser! {
+  pub grammar cql() for str {
+    rule comment()
+      = ("--" / "//") [^'\n']* / "/*" (!"*/" [_])* "*/"
+
+    rule _()
+      = quiet!{([' ' | '\t' | '\r' | '\n'] / comment())*}
+
+    rule kw(k: &'static str)
+      = w:$(['a'..='z' | 'A'..='Z' | '_']+) {? if w.eq_ignore_ascii_case(k) { Ok(()) } else { Err(k) } }
+
+    rule string()
+      = "'" ("''" / [^'\''])* "'" / "$$" (!"$$" [_])* "$$"
+
+    // Unquoted identifiers are case-insensitive and stored in lowercase
+    rule identifier() -> String
+      = "\"" s:$(("\"\"" / [^'"'])*) "\"" { s.replace("\"\"", "\"") }
+      / s:$(['a'..='z' | 'A'..='Z' | '_']['a'..='z' | 'A'..='Z' | '0'..='9' | '_']*) { s.to_lowercase() }
+
+    // A possibly qualified name, keeping only the final component
+    rule name() -> String
+      = names:(identifier() ++ (_ "." _)) { names.last().unwrap().clone() }
+
+    rule cql_type() -> String
+      = t:$(identifier() (_ "<" _ cql_type() ++ (_ "," _) _ ">")?) {
+          t.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_lowercase()
+        }
+
+    rule column() -> Element
+      = name:identifier() _ column_type:cql_type() _
+        is_static:(kw("static") _)? primary:(kw("primary") _ kw("key"))? {
+          Element::Column(name, column_type, is_static.is_some(), primary.is_some())
+        }
+
+    rule partition_key() -> Vec<String>
+      = "(" _ key:(identifier() ++ (_ "," _)) _ ")" { key }
+      / key:identifier() { vec![key] }
+
+    rule primary_key() -> Element
+      = kw("primary") _ kw("key") _ "(" _ partition:partition_key()
+        clustering:(_ "," _ c:(identifier() ++ (_ "," _)) { c })? _ ")" {
+          Element::PrimaryKey(partition, clustering.unwrap_or_default())
+        }
+
+    rule create_table_prefix()
+      = kw("create") _ (kw("table") / kw("columnfamily")) _ (kw("if") _ kw("not") _ kw("exists") _)?
+
+    rule create_table() -> Option<(String, Vec<Element>)>
+      = create_table_prefix() table:name() _
+        "(" _ elements:((primary_key() / column()) ++ (_ "," _)) _ ("," _)? ")"
+        (string() / comment() / [^';'])* {
+          Some((table, elements))
+        }
+
+    // Any other statement is ignored, but tables must be parsed
+    rule other()
+      = !create_table_prefix() (string() / comment() / [^';'])+
+
+    rule statement() -> Option<(String, Vec<Element>)>
+      = create_table() / other() { None }
+
+    pub rule script() -> Vec<(String, Vec<Element>)>
+      = _ statements:(s:statement()? _ ";" _ { s })* last:statement()? _ {
+          statements.into_iter().chain(Some(last)).flatten().flatten().collect()
+        }
+  }
+}
+
+/// Convert the name of a CQL type into a `FieldType`
+pub fn field_type(name: &str) -> FieldType {
+    match name {
+        "boolean" => FieldType::Boolean,
+        "tinyint" | "smallint" => FieldType::SmallInt,
+        "int" => FieldType::Integer,
+        "bigint" => FieldType::BigInt,
+        "float" => FieldType::Float,
+        "double" => FieldType::Double,
+        "decimal" => FieldType::Decimal(None, None),
+        "text" | "varchar" => FieldType::Text,
+        "blob" => FieldType::Binary,
+        "date" => FieldType::Date,
+        "time" => FieldType::Time,
+        "timestamp" => FieldType::Timestamp,
+        "uuid" => FieldType::Uuid,
+        _ => FieldType::Other(name.to_string()),
+    }
+}
+
+/// Parse CQL `CREATE TABLE` statements into tables whose keys are
+/// formed by the partition key and clustering columns
+pub fn parse(input: &str) -> Result<Input, peg::error::ParseError<peg::str::LineCol>> {
+    let mut parsed = Input {
+        tables: Vec::new(),
+        fds: Vec::new(),
+        inds: Vec::new(),
+        stats: Vec::new(),
+    };
+
+    for (name, elements) in cql::script(input)? {
+        let mut table = Table {
+            name: name.parse().unwrap(),
+            fields: IndexMap::new(),
+            ..Default::default()
+        };
+        let mut static_columns = Vec::new();
+
+        for element in elements {
+            match element {
+                Element::Column(column, column_type, is_static, primary) => {
+                    if is_static {
+                        static_columns.push(column.clone());
+                    }
+                    if primary {
+                        table.partition_key = vec![column.parse().unwrap()];
+                    }
+
+                    table.fields.insert(
+                        column.parse().unwrap(),
+                        Field {
+                            name: column.parse().unwrap(),
+                            key: false,
+                            cardinality: None,
+                            max_length: None,
+                            field_type: Some(field_type(&column_type)),
+                        },
+                    );
+                }
+                Element::PrimaryKey(partition, clustering) => {
+                    table.partition_key = partition.iter().map(|f| f.parse().unwrap()).collect();
+                    table.clustering_key = clustering.iter().map(|f| f.parse().unwrap()).collect();
+                }
+            }
+        }
+
+        for field in table.fields.values_mut() {
+            field.key = table.partition_key.contains(&field.name)
+                || table.clustering_key.contains(&field.name);
+        }
+        table.add_pk_fd();
+
+        // Static columns have a single value for each partition
+        if !static_columns.is_empty() {
+            let partition = table.partition_key.iter().map(|f| f.to_string()).collect();
+            parsed
+                .fds
+                .push((name.clone(), partition, static_columns, 1.0));
+        }
+
+        parsed.tables.push(table);
+    }
+
+    Ok(parsed)
+}
+
+/// Quote an identifier if it would not keep its case when unquoted
+fn quote(name: &str) -> String {
+    let plain = name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// The name of the CQL type used for a field
+fn type_name(field: &Field) -> String {
+    match field.field_type {
+        Some(FieldType::Boolean) => "boolean".to_string(),
+        Some(FieldType::SmallInt) => "smallint".to_string(),
+        Some(FieldType::Integer) => "int".to_string(),
+        Some(FieldType::BigInt) => "bigint".to_string(),
+        Some(FieldType::Float) => "float".to_string(),
+        Some(FieldType::Double) => "double".to_string(),
+        Some(FieldType::Decimal(_, _)) => "decimal".to_string(),
+        Some(FieldType::Binary) => "blob".to_string(),
+        Some(FieldType::Date) => "date".to_string(),
+        Some(FieldType::Time) => "time".to_string(),
+        Some(FieldType::Timestamp) => "timestamp".to_string(),
+        Some(FieldType::Uuid) => "uuid".to_string(),
+        Some(FieldType::Other(ref name)) => name.clone(),
+        Some(FieldType::Char(_))
+        | Some(FieldType::Varchar(_))
+        | Some(FieldType::Text)
+        | Some(FieldType::Json)
+        | None => "text".to_string(),
+    }
+}
+
+/// Split the key of a table into partition and clustering keys, using
+/// the layout given on input for any fields which are still keys
+fn key_layout(table: &Table) -> (Vec<FieldName>, Vec<FieldName>) {
+    let mut keys = table
+        .fields
+        .values()
+        .filter(|f| f.key)
+        .map(|f| f.name.clone())
+        .collect::<Vec<_>>();
+
+    // Cassandra requires a primary key, so use every field if needed
+    if keys.is_empty() {
+        keys = table.fields.keys().cloned().collect();
+    }
+
+    let mut partition = table
+        .partition_key
+        .iter()
+        .filter(|f| keys.contains(f))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut clustering = table
+        .clustering_key
+        .iter()
+        .filter(|f| keys.contains(f) && !partition.contains(f))
+        .cloned()
+        .collect::<Vec<_>>();
+    let rest = keys
+        .into_iter()
+        .filter(|f| !partition.contains(f) && !clustering.contains(f))
+        .collect::<Vec<_>>();
+
+    // Other keys are used to partition unless a partition key is known
+    if partition.is_empty() {
+        partition = rest;
+    } else {
+        clustering.extend(rest);
+    }
+    if partition.is_empty() && !clustering.is_empty() {
+        partition.push(clustering.remove(0));
+    }
+
+    (partition, clustering)
+}
+
+/// Format the `PRIMARY KEY` clause for a partition and clustering key
+fn primary_key(partition: &[FieldName], clustering: &[FieldName]) -> String {
+    let partition = if partition.len() == 1 {
+        quote(partition[0].as_ref())
+    } else {
+        format!(
+            "({})",
+            partition.iter().map(|f| quote(f.as_ref())).join(", ")
+        )
+    };
+    let key = Some(partition)
+        .into_iter()
+        .chain(clustering.iter().map(|f| quote(f.as_ref())))
+        .join(", ");
+    format!("PRIMARY KEY ({})", key)
+}
+
+/// Write a `CREATE TABLE` statement for a table with the given name
+fn create_table(table: &Table, name: &str) -> String {
+    let (partition, clustering) = key_layout(table);
+    let lines = table
+        .fields
+        .values()
+        .map(|f| format!("  {} {}", quote(f.name.as_ref()), type_name(f)))
+        .chain(Some(format!("  {}", primary_key(&partition, &clustering))))
+        .collect::<Vec<_>>();
+    format!(
+        "CREATE TABLE {} (\n{}\n);\n",
+        quote(name),
+        lines.join(",\n")
+    )
+}
+
+/// Describe how the access path of an input table which was decomposed
+/// can be rebuilt from the tables derived from it
+fn access_path(
+    input_table: &Table,
+    derived: &[&Table],
+    names: &HashMap<TableName, String>,
+) -> String {
+    let mut output = format!(
+        "{} was decomposed into {}.\n",
+        input_table.name,
+        derived.iter().map(|t| &names[&t.name]).join(", ")
+    );
+    let (partition, clustering) = key_layout(input_table);
+    let view_key = partition
+        .iter()
+        .chain(clustering.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    // A materialized view can be used when a single table holds every
+    // field and the view key includes at most one non-key field
+    let base = derived.iter().find(|t| {
+        let keys = t.key_fields();
+        input_table.fields.keys().all(|f| t.fields.contains_key(f))
+            && keys.iter().all(|f| input_table.fields.contains_key(f))
+            && view_key.iter().filter(|f| !keys.contains(*f)).count() <= 1
+    });
+
+    let statement = match base {
+        Some(base) => {
+            let mut clustering = clustering.clone();
+            for key in base.fields.values().filter(|f| f.key) {
+                if !view_key.contains(&key.name) {
+                    clustering.push(key.name.clone());
+                }
+            }
+            let fields = input_table.fields.keys().map(|f| quote(f.as_ref()));
+            let not_null = partition
+                .iter()
+                .chain(clustering.iter())
+                .map(|f| format!("{} IS NOT NULL", quote(f.as_ref())));
+            output.push_str("The original access path can be served by a materialized view:\n");
+            format!(
+                "CREATE MATERIALIZED VIEW {} AS\n  SELECT {}\n  FROM {}\n  WHERE {}\n  {};\n",
+                quote(&names[&input_table.name]),
+                fields.format(", "),
+                quote(&names[&base.name]),
+                not_null.format(" AND "),
+                primary_key(&partition, &clustering)
+            )
+        }
+        None => {
+            output.push_str(
+                "The original access path can be served by a denormalized table\n\
+                 maintained by the application with data from these tables:\n",
+            );
+            create_table(input_table, &names[&input_table.name])
+        }
+    };
+    output.push_str(&statement);
+
+    output.lines().map(|l| format!("-- {}\n", l)).collect()
+}
+
+/// Write `CREATE TABLE` statements for all tables in a schema along with
+/// a description of how to rebuild each input table which was decomposed
+pub fn write_schema(schema: &Schema, input_tables: &[Table]) -> String {
+    // Keep table and view names within the length limit of Cassandra
+    let names = short_table_names(
+        schema
+            .tables
+            .keys()
+            .chain(input_tables.iter().map(|t| &t.name)),
+        Some(MAX_TABLE_NAME_LENGTH),
+    );
+
+    let mut output = String::new();
+    for table in schema.tables.values().sorted_by_key(|t| t.name.clone()) {
+        output.push_str(&create_table(table, &names[&table.name]));
+        output.push('\n');
+    }
+
+    for input_table in input_tables.iter().sorted_by_key(|t| t.name.clone()) {
+        if schema.tables.contains_key(&input_table.name) {
+            continue;
+        }
+
+        let derived = schema
+            .tables
+            .values()
+            .filter(|t| t.source_names().contains(&input_table.name))
+            .sorted_by_key(|t| t.name.clone())
+            .collect::<Vec<_>>();
+        if !derived.is_empty() {
+            output.push_str(&access_path(input_table, &derived, &names));
+            output.push('\n');
+        }
+    }
+
+    output.trim_end().to_string() + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dependencies::IND;
+    use crate::normalize::Normalizer;
+    use crate::symbols::TableName;
+
+    #[test]
+    fn parse_create_table() {
+        let parsed = parse(
+            "CREATE KEYSPACE rubis WITH replication = {'class': 'SimpleStrategy'};\n\
+             CREATE TABLE rubis.Bids_By_User (\n\
+               user_id uuid,\n\
+               \"ItemId\" int,\n\
+               bid_id timeuuid,\n\
+               nickname text STATIC, // shared by the partition\n\
+               tags frozen<map<text, int>>,\n\
+               PRIMARY KEY ((user_id, \"ItemId\"), bid_id)\n\
+             ) WITH CLUSTERING ORDER BY (bid_id DESC);\n\
+             create table users (id uuid primary key, name text)",
+        )
+        .unwrap();
+
+        let bids = &parsed.tables[0];
+        assert_eq!(
+            bids.to_string(),
+            "bids_by_user(*ItemId, *bid_id, *user_id, nickname, tags)"
+        );
+        assert_eq!(bids.partition_key, field_vec!["user_id", "ItemId"]);
+        assert_eq!(bids.clustering_key, field_vec!["bid_id"]);
+        assert_eq!(
+            bids.fields[&FieldName::from("tags")].field_type,
+            Some(FieldType::Other("frozen<map<text,int>>".to_string()))
+        );
+        assert_eq!(
+            parsed.fds,
+            vec![(
+                "bids_by_user".to_string(),
+                vec!["user_id".to_string(), "ItemId".to_string()],
+                vec!["nickname".to_string()],
+                1.0
+            )]
+        );
+
+        let users = &parsed.tables[1];
+        assert_eq!(users.to_string(), "users(*id, name)");
+        assert_eq!(users.partition_key, field_vec!["id"]);
+        assert!(users.clustering_key.is_empty());
+    }
+
+    #[test]
+    fn parse_invalid_table() {
+        assert!(parse("CREATE TABLE t (id int, PRIMARY KEY id);").is_err());
+    }
+
+    #[test]
+    fn write_decomposed() {
+        let parsed = parse(
+            "CREATE TABLE bids_by_user (\n\
+               user_id uuid, bid_id timeuuid, nickname text STATIC, qty int,\n\
+               PRIMARY KEY (user_id, bid_id)\n\
+             );",
+        )
+        .unwrap();
+        let input_tables = parsed.tables.clone();
+        let mut schema = parsed.into_schema();
+        let normalizer = Normalizer {
+            use_stats: false,
+            fd_threshold: None,
+            min_confidence: None,
+        };
+        normalizer.normalize(&mut schema);
+
+        assert_eq!(
+            write_schema(&schema, &input_tables),
+            "CREATE TABLE bids_by_user_base (\n  user_id uuid,\n  bid_id timeuuid,\n  \
+             qty int,\n  PRIMARY KEY (user_id, bid_id)\n);\n\n\
+             CREATE TABLE bids_by_user_ext (\n  user_id uuid,\n  nickname text,\n  \
+             PRIMARY KEY (user_id)\n);\n\n\
+             -- bids_by_user was decomposed into bids_by_user_base, bids_by_user_ext.\n\
+             -- The original access path can be served by a denormalized table\n\
+             -- maintained by the application with data from these tables:\n\
+             -- CREATE TABLE bids_by_user (\n\
+             --   user_id uuid,\n\
+             --   bid_id timeuuid,\n\
+             --   nickname text,\n\
+             --   qty int,\n\
+             --   PRIMARY KEY (user_id, bid_id)\n\
+             -- );\n"
+        );
+    }
+
+    #[test]
+    fn write_materialized_view() {
+        let t1 = table!(
+            "foo",
+            fields! {
+              field!("bar", true),
+              field!("baz")
+            }
+        );
+        let t2 = table!(
+            "qux",
+            fields! {
+              field!("quux", true),
+              field!("corge")
+            }
+        );
+        let input_tables = vec![t1.clone(), t2.clone()];
+        let mut schema = schema! {t1, t2};
+        add_ind!(schema, "foo", ["bar"], "qux", ["quux"]);
+        add_ind!(schema, "qux", ["quux"], "foo", ["bar"]);
+        let normalizer = Normalizer {
+            use_stats: false,
+            fd_threshold: None,
+            min_confidence: None,
+        };
+        assert!(normalizer.subsume(&mut schema));
+
+        // The merged table holds every field of foo but not of qux
+        let output = write_schema(&schema, &input_tables);
+        assert!(output.contains(
+            "-- foo was decomposed into foo_qux.\n\
+             -- The original access path can be served by a materialized view:\n\
+             -- CREATE MATERIALIZED VIEW foo AS\n\
+             --   SELECT bar, baz\n\
+             --   FROM foo_qux\n\
+             --   WHERE bar IS NOT NULL\n\
+             --   PRIMARY KEY (bar);\n"
+        ));
+        assert!(output.contains("-- qux was decomposed into foo_qux.\n"));
+    }
+
+    #[test]
+    fn write_long_names() {
+        let input_tables = vec![table!(
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            fields! {
+              field!("bar", true),
+              field!("baz")
+            }
+        )];
+        let mut table = table!(
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa_ext",
+            fields! {
+              field!("bar", true),
+              field!("baz")
+            }
+        );
+        table.sources = vec![input_tables[0].name.clone()];
+        let schema = schema! {table};
+
+        // Names are cut to 48 characters and kept unique
+        let output = write_schema(&schema, &input_tables);
+        assert!(
+            output.starts_with("CREATE TABLE aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa_2 (\n")
+        );
+        assert!(output.contains("-- aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa was decomposed into aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa_2.\n"));
+        assert!(output.contains(
+            "-- CREATE MATERIALIZED VIEW aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa AS\n"
+        ));
+    }
+
+    #[test]
+    fn write_key_layout() {
+        let mut table = table!(
+            "Foo",
+            fields! {
+              field!("a", true),
+              field!("b", true),
+              field!("c", true),
+              field!("d")
+            }
+        );
+        assert_eq!(
+            primary_key(&key_layout(&table).0, &key_layout(&table).1),
+            "PRIMARY KEY ((a, b, c))"
+        );
+
+        table.partition_key = field_vec!["b"];
+        table.clustering_key = field_vec!["c", "d"];
+        let (partition, clustering) = key_layout(&table);
+        assert_eq!(partition, field_vec!["b"]);
+        assert_eq!(clustering, field_vec!["c", "a"]);
+        assert!(create_table(&table, "Foo").starts_with("CREATE TABLE \"Foo\" ("));
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use indexmap::IndexSet;
+use serde::de::Error;
+use serde_json::Value;
+
+/// The rows of a single table read from a data file
+pub struct Relation {
+    /// The name of the table
+    pub name: String,
+
+    /// Names of the columns in order
+    pub columns: Vec<String>,
+
+    /// Values of each row in the order of the columns
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Relation {
+    /// Produce the values of a column in every row
+    pub fn column(&self, index: usize) -> impl Iterator<Item = &str> {
+        self.rows.iter().map(move |row| row[index].as_str())
+    }
+
+    /// Compute the number of rows along with the number of distinct
+    /// values and maximum length of each column, ignoring empty values
+    pub fn stats(&self) -> Vec<(String, Option<String>, usize, Option<usize>)> {
+        let mut stats = vec![(self.name.clone(), None, self.rows.len(), None)];
+        for (i, column) in self.columns.iter().enumerate() {
+            let values = self
+                .column(i)
+                .filter(|v| !v.is_empty())
+                .collect::<HashSet<_>>();
+            let max_length = values.iter().map(|v| v.chars().count()).max();
+            stats.push((
+                self.name.clone(),
+                Some(column.clone()),
+                values.len(),
+                Some(max_length.unwrap_or(0)),
+            ));
+        }
+
+        stats
+    }
+}
+
+/// Find the name of a table from the name of its data file
+pub fn table_name(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(path)
+        .to_string()
+}
+
+/// Read a CSV file with a header row into a `Relation`
+pub fn read_csv(path: &str) -> Result<Relation, csv::Error> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let columns = reader
+        .headers()?
+        .iter()
+        .map(|h| h.to_string())
+        .collect::<Vec<_>>();
+
+    // Columns become fields, so each needs a distinct name
+    let mut seen = HashSet::new();
+    if let Some(column) = columns.iter().find(|c| !seen.insert(*c)) {
+        return Err(csv::Error::from(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("duplicate column {}", column),
+        )));
+    }
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        rows.push(record?.iter().map(|v| v.to_string()).collect());
+    }
+
+    Ok(Relation {
+        name: table_name(path),
+        columns,
+        rows,
+    })
+}
+
+/// Convert a JSON value to the text stored in a relation
+fn value_text(value: Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s,
+        other => other.to_string(),
+    }
+}
+
+/// Read a file with one JSON object per line into a `Relation` with a
+/// column for every key, leaving values empty where keys are missing
+pub fn read_json_lines(path: &str) -> Result<Relation, serde_json::Error> {
+    let file = File::open(path).map_err(serde_json::Error::io)?;
+    let mut objects = Vec::new();
+    let mut columns = IndexSet::new();
+    for value in serde_json::Deserializer::from_reader(BufReader::new(file)).into_iter::<Value>() {
+        let object = match value? {
+            Value::Object(object) => object,
+            other => {
+                return Err(serde_json::Error::custom(format!(
+                    "expected an object but found {}",
+                    other
+                )))
+            }
+        };
+        for key in object.keys() {
+            columns.insert(key.clone());
+        }
+        objects.push(object);
+    }
+
+    let columns = columns.into_iter().collect::<Vec<String>>();
+    let rows = objects
+        .into_iter()
+        .map(|mut object| {
+            columns
+                .iter()
+                .map(|c| object.remove(c).map(value_text).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+
+    Ok(Relation {
+        name: table_name(path),
+        columns,
+        rows,
+    })
+}
+
+/// Read a data file as JSON lines or CSV depending on its extension
+pub fn read_relation(path: &str) -> Result<Relation, String> {
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("jsonl") | Some("ndjson") => read_json_lines(path).map_err(|e| e.to_string()),
+        _ => read_csv(path).map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn relation_stats() {
+        let relation = Relation {
+            name: "users".to_string(),
+            columns: vec!["id".to_string(), "name".to_string()],
+            rows: vec![
+                vec!["1".to_string(), "alice".to_string()],
+                vec!["2".to_string(), "".to_string()],
+                vec!["3".to_string(), "alice".to_string()],
+            ],
+        };
+        assert_eq!(
+            relation.stats(),
+            vec![
+                ("users".to_string(), None, 3, None),
+                ("users".to_string(), Some("id".to_string()), 3, Some(1)),
+                ("users".to_string(), Some("name".to_string()), 1, Some(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_lines_columns() {
+        let path = env::temp_dir().join("eson_users.jsonl");
+        fs::write(
+            &path,
+            "{\"id\": 1, \"name\": \"alice\"}\n{\"id\": 2, \"name\": null, \"tags\": [1]}\n",
+        )
+        .unwrap();
+        let relation = read_json_lines(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(relation.name, "eson_users");
+        assert_eq!(relation.columns, vec!["id", "name", "tags"]);
+        assert_eq!(
+            relation.rows,
+            vec![vec!["1", "alice", ""], vec!["2", "", "[1]"]]
+        );
+    }
+
+    #[test]
+    fn csv_duplicate_columns() {
+        let path = env::temp_dir().join("eson_dup.csv");
+        fs::write(&path, "a,b,a\n1,2,3\n").unwrap();
+        let err = read_csv(path.to_str().unwrap()).err().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(err.to_string(), "duplicate column a");
+    }
+}
@@ -1,10 +1,12 @@
 use log::Level::Info;
+use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 
 extern crate group_by;
 extern crate permutation;
 
+use float_ord::FloatOrd;
 use itertools::Itertools;
 
 use crate::model::Schema;
@@ -12,105 +14,326 @@
 use crate::model::{Field, Table};
 use crate::symbols::{FieldName, TableName};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct FD {
     pub lhs: HashSet<FieldName>,
     pub rhs: HashSet<FieldName>,
+
+    /// The fraction of rows on which the dependency holds,
+    /// which is 1 for exact dependencies
+    pub confidence: f32,
 }
 
 impl fmt::Display for FD {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let lhs = self.lhs.iter().join(", ");
         let rhs = self.rhs.iter().join(", ");
-        write!(f, "{} -> {}", lhs, rhs)
+        write!(f, "{} -> {}", lhs, rhs)?;
+        if !self.is_exact() {
+            write!(f, " @{}", self.confidence)?;
+        }
+
+        Ok(())
     }
 }
 
+/// Combine the confidence of two FDs used to infer another, assuming the
+/// rows violating each are distinct so the error rates add
+pub fn combined_confidence(first: f32, second: f32) -> f32 {
+    // Round to avoid errors from adding binary fractions in the output
+    let confidence = f64::max(0.0, first as f64 + second as f64 - 1.0);
+    ((confidence * 1e6).round() / 1e6) as f32
+}
+
 impl FD {
     /// Check if this `FD` is trivial
     pub fn is_trivial(&self) -> bool {
         self.rhs.is_subset(&self.lhs)
     }
 
+    /// Check if this `FD` holds on every row
+    pub fn is_exact(&self) -> bool {
+        self.confidence >= 1.0
+    }
+
     /// Produce a new `FD` with the left and right sides switched
     pub fn reverse(&self) -> FD {
         FD {
             lhs: self.rhs.clone(),
             rhs: self.lhs.clone(),
+            confidence: self.confidence,
         }
     }
 }
 
-pub trait FDClosure {
-    fn closure(&mut self) -> bool;
+/// Compute the closure of a set of fields under some FDs (`X+`) along
+/// with the confidence with which each field in the closure is determined
+///
+/// Repeatedly applying each FD whose left-hand side is in the closure is
+/// complete for Armstrong's axioms since augmentation and transitivity
+/// are both covered by allowing any subset of the closure as the LHS.
+pub fn field_closure<'a, I>(fds: I, fields: &HashSet<FieldName>) -> HashMap<FieldName, f32>
+where
+    I: IntoIterator<Item = &'a FD>,
+    I::IntoIter: Clone,
+{
+    let fds = fds.into_iter();
+    let mut closure = fields
+        .iter()
+        .map(|f| (f.clone(), 1.0))
+        .collect::<HashMap<_, _>>();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for fd in fds.clone() {
+            // Every field on the LHS must already be determined and the
+            // inferred fields are only as certain as the weakest of them
+            let lhs_confidence = fd.lhs.iter().try_fold(1.0, |confidence: f32, f| {
+                closure.get(f).map(|&c| f32::min(confidence, c))
+            });
+            let confidence = match lhs_confidence {
+                Some(c) if c >= 1.0 => fd.confidence,
+                Some(c) => combined_confidence(c, fd.confidence),
+                None => continue,
+            };
+            if confidence <= 0.0 {
+                continue;
+            }
+
+            for field in &fd.rhs {
+                if closure.get(field).is_none_or(|&c| c < confidence) {
+                    closure.insert(field.clone(), confidence);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    closure
 }
 
-impl FDClosure for HashMap<Vec<FieldName>, FD> {
-    fn closure(&mut self) -> bool {
-        let mut any_changed = false;
-        let mut changed = true;
+/// Find the FDs which are applied in order to derive the closure of a
+/// set of fields along with the new fields each of them adds
+pub fn closure_proof<'a, I>(fds: I, fields: &HashSet<FieldName>) -> Vec<(&'a FD, Vec<FieldName>)>
+where
+    I: IntoIterator<Item = &'a FD>,
+    I::IntoIter: Clone,
+{
+    let fds = fds.into_iter();
+    let mut closure = fields.clone();
+    let mut proof = Vec::new();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for fd in fds.clone() {
+            if !fd.lhs.is_subset(&closure) {
+                continue;
+            }
 
-        while changed {
-            info!("FD closure loop...");
+            let added = fd
+                .rhs
+                .iter()
+                .filter(|f| !closure.contains(*f))
+                .cloned()
+                .sorted()
+                .collect::<Vec<_>>();
+            if !added.is_empty() {
+                closure.extend(added.iter().cloned());
+                proof.push((fd, added));
+                changed = true;
+            }
+        }
+    }
 
-            changed = false;
-            let mut new_fds = Vec::new();
+    proof
+}
 
-            for fd1 in self.values() {
-                for fd2 in self.values() {
-                    // Check if a new FD can be inferred via transitivity
-                    if fd1 == fd2 || !fd2.lhs.is_subset(&fd1.rhs) {
-                        continue;
-                    }
+/// Check if every field on the RHS of an `FD` is in a closure with
+/// at least the confidence of the `FD`
+fn closure_implies(closure: &HashMap<FieldName, f32>, fd: &FD) -> bool {
+    fd.rhs
+        .iter()
+        .all(|f| closure.get(f).is_some_and(|&c| c >= fd.confidence))
+}
+
+/// Order FDs with a single field on the RHS by their fields so that the
+/// most certain of any duplicates comes first
+fn sort_singleton_fds(fds: &mut [FD]) {
+    fds.sort_by_key(|fd| {
+        (
+            fd.lhs.iter().cloned().sorted().collect::<Vec<_>>(),
+            fd.rhs.iter().cloned().collect::<Vec<_>>(),
+            Reverse(FloatOrd(fd.confidence)),
+        )
+    });
+}
 
-                    let mut lhs_copy = fd1.lhs.clone().into_iter().collect::<Vec<_>>();
-                    lhs_copy.sort();
+/// Compute a minimal cover of some FDs, which is an equivalent set of
+/// FDs with a single field on each RHS, no extraneous fields on any LHS
+/// and no FD which is implied by the others
+pub fn minimal_cover<'a, I>(fds: I) -> Vec<FD>
+where
+    I: IntoIterator<Item = &'a FD>,
+{
+    let mut cover = fds
+        .into_iter()
+        .flat_map(|fd| {
+            fd.rhs
+                .iter()
+                .filter(move |f| !fd.lhs.contains(*f))
+                .map(move |f| FD {
+                    lhs: fd.lhs.clone(),
+                    rhs: Some(f.clone()).into_iter().collect(),
+                    confidence: fd.confidence,
+                })
+        })
+        .collect::<Vec<_>>();
+    sort_singleton_fds(&mut cover);
+    cover.dedup_by(|a, b| a.lhs == b.lhs && a.rhs == b.rhs);
+
+    // Remove fields from each LHS which are not needed to imply the RHS
+    for i in 0..cover.len() {
+        for field in cover[i].lhs.iter().cloned().sorted().collect::<Vec<_>>() {
+            let mut lhs = cover[i].lhs.clone();
+            lhs.remove(&field);
+            if !lhs.is_empty() && closure_implies(&field_closure(&cover, &lhs), &cover[i]) {
+                debug!("Removing {} from the LHS of {}", field, cover[i]);
+                cover[i].lhs = lhs;
+            }
+        }
+    }
+    sort_singleton_fds(&mut cover);
+    cover.dedup_by(|a, b| a.lhs == b.lhs && a.rhs == b.rhs);
+
+    // Remove FDs which are implied by the remaining FDs
+    let mut i = 0;
+    while i < cover.len() {
+        let fd = cover.remove(i);
+        if closure_implies(&field_closure(&cover, &fd.lhs), &fd) {
+            debug!("Removing redundant {}", fd);
+        } else {
+            cover.insert(i, fd);
+            i += 1;
+        }
+    }
 
-                    let new_fd = if self.contains_key(&lhs_copy) {
-                        let mut new_rhs = self.get(&lhs_copy).unwrap().rhs.clone();
-                        new_rhs.extend(fd2.rhs.clone().into_iter());
-                        new_rhs.retain(|f| !lhs_copy.contains(f));
+    cover
+}
 
-                        FD {
-                            lhs: fd1.lhs.clone(),
-                            rhs: new_rhs,
-                        }
-                    } else {
-                        FD {
-                            lhs: fd1.lhs.clone(),
-                            rhs: fd2
-                                .rhs
-                                .clone()
-                                .into_iter()
-                                .filter(|f| !fd1.lhs.contains(f))
-                                .collect::<HashSet<_>>(),
-                        }
-                    };
+/// Compute a canonical cover of some FDs, which is a minimal cover
+/// where FDs with the same LHS are merged
+pub fn canonical_cover<'a, I>(fds: I) -> Vec<FD>
+where
+    I: IntoIterator<Item = &'a FD>,
+{
+    let mut cover: Vec<FD> = Vec::new();
+    for fd in minimal_cover(fds) {
+        // The minimal cover is sorted so FDs with the same LHS are adjacent
+        match cover.last_mut() {
+            Some(last) if last.lhs == fd.lhs => {
+                last.rhs.extend(fd.rhs);
+                last.confidence = f32::min(last.confidence, fd.confidence);
+            }
+            _ => cover.push(fd),
+        }
+    }
 
-                    // Ensure the new FD actually adds fields
-                    if !self.contains_key(&lhs_copy)
-                        || !new_fd.rhs.is_subset(&self.get(&lhs_copy).unwrap().rhs)
-                    {
-                        new_fds.push(new_fd);
-                    }
-                }
+    cover
+}
+
+/// Find every candidate key of a table with some fields given the FDs
+/// which hold on it using the algorithm of Lucchesi and Osborn
+///
+/// Only exact FDs are used since approximate FDs do not always hold.
+/// Each key lists its fields in the order given and keys are ordered
+/// by the number of fields and then by the positions of their fields.
+pub fn candidate_keys<'a, I>(fds: I, fields: &[FieldName]) -> Vec<Vec<FieldName>>
+where
+    I: IntoIterator<Item = &'a FD>,
+    I::IntoIter: Clone,
+{
+    let fds = fds.into_iter().filter(|fd| fd.confidence >= 1.0);
+    let is_superkey = |key: &HashSet<FieldName>| {
+        let closure = field_closure(fds.clone(), key);
+        fields.iter().all(|f| closure.contains_key(f))
+    };
+
+    // Remove fields from a superkey until it is minimal, keeping the
+    // fields which come first where possible
+    let reduce = |mut key: HashSet<FieldName>| {
+        for field in fields.iter().rev() {
+            if key.remove(field) && !is_superkey(&key) {
+                key.insert(field.clone());
+            }
+        }
+        key
+    };
+
+    let mut keys = vec![reduce(fields.iter().cloned().collect())];
+    let mut i = 0;
+    while i < keys.len() {
+        for fd in fds.clone() {
+            // Replacing the RHS of an FD in a key with its LHS produces a
+            // superkey which contains a new key if it contains no others
+            let superkey = keys[i]
+                .difference(&fd.rhs)
+                .chain(fd.lhs.iter())
+                .cloned()
+                .collect::<HashSet<_>>();
+            if !keys.iter().any(|k| k.is_subset(&superkey)) {
+                let key = reduce(superkey);
+                debug!("Found candidate key {}", key.iter().sorted().join(", "));
+                keys.push(key);
             }
+        }
+        i += 1;
+    }
 
-            // Add any new FDs which were discovered
-            if !new_fds.is_empty() {
-                for new_fd in new_fds {
-                    let mut lhs_copy = new_fd.lhs.clone().into_iter().collect::<Vec<_>>();
-                    lhs_copy.sort();
+    keys.into_iter()
+        .map(|key| {
+            fields
+                .iter()
+                .filter(|f| key.contains(*f))
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .sorted_by_key(|key| {
+            let positions = key
+                .iter()
+                .map(|f| fields.iter().position(|f2| f2 == f))
+                .collect::<Vec<_>>();
+            (key.len(), positions)
+        })
+        .collect()
+}
 
-                    if !self.contains_key(&lhs_copy) || self[&lhs_copy] != new_fd {
-                        changed = true;
-                        debug!("Inferred {} via transitivity", new_fd);
-                        self.insert(lhs_copy, new_fd);
-                    }
+pub trait FDClosure {
+    fn closure(&mut self) -> bool;
+}
+
+impl FDClosure for HashMap<Vec<FieldName>, FD> {
+    /// Extend the RHS of every FD to the closure of its LHS so each FD
+    /// contains every field implied by its LHS
+    fn closure(&mut self) -> bool {
+        info!("FD closure...");
+
+        let fds = self.values().cloned().collect::<Vec<_>>();
+        let mut any_changed = false;
+        for fd in self.values_mut() {
+            // Merged FDs are only as certain as the weakest
+            let mut inferred = fd.clone();
+            for (field, confidence) in field_closure(&fds, &fd.lhs) {
+                if !fd.lhs.contains(&field) && !fd.rhs.contains(&field) {
+                    inferred.rhs.insert(field);
+                    inferred.confidence = f32::min(inferred.confidence, confidence);
                 }
             }
 
-            if changed {
+            if inferred.rhs != fd.rhs {
+                debug!("Inferred {} via closure", inferred);
+                *fd = inferred;
                 any_changed = true;
             }
         }
@@ -351,9 +574,15 @@ fn fd_fmt() {
         let fd = FD {
             lhs: field_set!["foo"],
             rhs: field_set!["bar"],
+            confidence: 1.0,
         };
-
         assert_eq!("foo -> bar", format!("{}", fd));
+
+        let approximate = FD {
+            confidence: 0.98,
+            ..fd
+        };
+        assert_eq!("foo -> bar @0.98", format!("{}", approximate));
     }
 
     #[test]
@@ -361,6 +590,7 @@ fn fd_trivial() {
         let fd = FD {
             lhs: field_set!["foo", "bar"],
             rhs: field_set!["bar"],
+            confidence: 1.0,
         };
         assert!(fd.is_trivial());
     }
@@ -370,6 +600,7 @@ fn fd_reverse() {
         let fd = FD {
             lhs: field_set!["foo"],
             rhs: field_set!["bar"],
+            confidence: 1.0,
         };
         let reverse = fd.reverse();
         assert_eq!(reverse.lhs, fd.rhs);
@@ -381,11 +612,13 @@ fn fd_closure() {
         let mut fds: HashMap<Vec<FieldName>, FD> = collect![
           field_vec!["foo"] => FD {
             lhs: field_set!["foo"],
-            rhs: field_set!["bar"]
+            rhs: field_set!["bar"],
+            confidence: 1.0
           },
           field_vec!["bar"] => FD {
             lhs: field_set!["bar"],
-            rhs: field_set!["baz"]
+            rhs: field_set!["baz"],
+            confidence: 1.0
           }
         ];
         assert!(fds.closure());
@@ -393,11 +626,219 @@ fn fd_closure() {
         assert!(fds.values().any(|fd| *fd
             == FD {
                 lhs: field_set!["foo"],
-                rhs: field_set!["bar", "baz"]
+                rhs: field_set!["bar", "baz"],
+                confidence: 1.0
             }));
         assert!(!fds.closure());
     }
 
+    #[test]
+    fn fd_closure_confidence() {
+        let mut fds: HashMap<Vec<FieldName>, FD> = collect![
+          field_vec!["foo"] => FD {
+            lhs: field_set!["foo"],
+            rhs: field_set!["bar"],
+            confidence: 0.98
+          },
+          field_vec!["bar"] => FD {
+            lhs: field_set!["bar"],
+            rhs: field_set!["baz"],
+            confidence: 0.97
+          }
+        ];
+        assert!(fds.closure());
+
+        let inferred = &fds[&field_vec!["foo"]];
+        assert_eq!(inferred.rhs, field_set!["bar", "baz"]);
+        assert_eq!(inferred.confidence, 0.95);
+        assert!(!fds.closure());
+    }
+
+    #[test]
+    fn fd_closure_augmentation() {
+        let mut fds: HashMap<Vec<FieldName>, FD> = collect![
+          field_vec!["a"] => FD {
+            lhs: field_set!["a"],
+            rhs: field_set!["b"],
+            confidence: 1.0
+          },
+          field_vec!["a", "c"] => FD {
+            lhs: field_set!["a", "c"],
+            rhs: field_set!["d"],
+            confidence: 1.0
+          },
+          field_vec!["b", "c"] => FD {
+            lhs: field_set!["b", "c"],
+            rhs: field_set!["e"],
+            confidence: 1.0
+          }
+        ];
+        assert!(fds.closure());
+
+        assert_eq!(fds[&field_vec!["a"]].rhs, field_set!["b"]);
+        assert_eq!(fds[&field_vec!["a", "c"]].rhs, field_set!["b", "d", "e"]);
+        assert_eq!(fds[&field_vec!["b", "c"]].rhs, field_set!["e"]);
+        assert!(!fds.closure());
+    }
+
+    #[test]
+    fn field_closure_confidence() {
+        let fds = vec![
+            FD {
+                lhs: field_set!["a"],
+                rhs: field_set!["b"],
+                confidence: 0.9,
+            },
+            FD {
+                lhs: field_set!["b"],
+                rhs: field_set!["c"],
+                confidence: 0.9,
+            },
+            FD {
+                lhs: field_set!["a"],
+                rhs: field_set!["c"],
+                confidence: 0.95,
+            },
+        ];
+        let closure = field_closure(&fds, &field_set!["a"]);
+        assert_eq!(closure.len(), 3);
+        assert_eq!(closure[&FieldName::from("a")], 1.0);
+        assert_eq!(closure[&FieldName::from("b")], 0.9);
+
+        // The most certain way of inferring a field is used
+        assert_eq!(closure[&FieldName::from("c")], 0.95);
+    }
+
+    #[test]
+    fn closure_proof_steps() {
+        let fds = vec![
+            FD {
+                lhs: field_set!["a"],
+                rhs: field_set!["b"],
+                confidence: 1.0,
+            },
+            FD {
+                lhs: field_set!["b", "c"],
+                rhs: field_set!["a", "d"],
+                confidence: 1.0,
+            },
+            FD {
+                lhs: field_set!["e"],
+                rhs: field_set!["f"],
+                confidence: 1.0,
+            },
+        ];
+        let proof = closure_proof(&fds, &field_set!["a", "c"]);
+        assert_eq!(
+            proof,
+            vec![(&fds[0], field_vec!["b"]), (&fds[1], field_vec!["d"])]
+        );
+    }
+
+    #[test]
+    fn minimal_cover_fds() {
+        let fds = vec![
+            FD {
+                lhs: field_set!["a"],
+                rhs: field_set!["b", "c"],
+                confidence: 1.0,
+            },
+            FD {
+                lhs: field_set!["b"],
+                rhs: field_set!["c"],
+                confidence: 1.0,
+            },
+            FD {
+                lhs: field_set!["a", "b"],
+                rhs: field_set!["b", "d"],
+                confidence: 1.0,
+            },
+        ];
+        let cover = minimal_cover(&fds)
+            .into_iter()
+            .map(|fd| fd.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(cover, vec!["a -> b", "a -> d", "b -> c"]);
+    }
+
+    #[test]
+    fn canonical_cover_fds() {
+        let fds = vec![
+            FD {
+                lhs: field_set!["a"],
+                rhs: field_set!["b", "c", "d"],
+                confidence: 1.0,
+            },
+            FD {
+                lhs: field_set!["b"],
+                rhs: field_set!["a", "c"],
+                confidence: 1.0,
+            },
+        ];
+        let cover = canonical_cover(&fds);
+        assert_eq!(cover.len(), 2);
+        assert_eq!(cover[0].lhs, field_set!["a"]);
+        assert_eq!(cover[0].rhs, field_set!["b", "d"]);
+        assert_eq!(cover[1].lhs, field_set!["b"]);
+        assert_eq!(cover[1].rhs, field_set!["a", "c"]);
+    }
+
+    #[test]
+    fn minimal_cover_confidence() {
+        let fds = vec![
+            FD {
+                lhs: field_set!["a"],
+                rhs: field_set!["b"],
+                confidence: 0.9,
+            },
+            FD {
+                lhs: field_set!["b"],
+                rhs: field_set!["c"],
+                confidence: 0.9,
+            },
+            FD {
+                lhs: field_set!["a"],
+                rhs: field_set!["c"],
+                confidence: 0.95,
+            },
+        ];
+
+        // The FD on a -> c is more certain than the one which is inferred
+        assert_eq!(minimal_cover(&fds).len(), 3);
+    }
+
+    #[test]
+    fn candidate_keys_fds() {
+        let fds = vec![
+            FD {
+                lhs: field_set!["a"],
+                rhs: field_set!["b"],
+                confidence: 1.0,
+            },
+            FD {
+                lhs: field_set!["b", "c"],
+                rhs: field_set!["a", "d"],
+                confidence: 1.0,
+            },
+            FD {
+                lhs: field_set!["d"],
+                rhs: field_set!["c"],
+                confidence: 1.0,
+            },
+        ];
+        let fields = field_vec!["a", "b", "c", "d"];
+        let keys = candidate_keys(&fds, &fields);
+        assert_eq!(
+            keys,
+            vec![
+                field_vec!["a", "c"],
+                field_vec!["a", "d"],
+                field_vec!["b", "c"],
+                field_vec!["b", "d"]
+            ]
+        );
+    }
+
     #[test]
     fn ind_fmt() {
         let ind = IND {
@@ -0,0 +1,664 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use indexmap::IndexMap;
+
+use crate::data::Relation;
+use crate::dependencies::IND;
+use crate::input::Input;
+use crate::model::{Field, Table};
+use crate::symbols::FieldName;
+
+/// A set of columns represented by the bits of their indexes
+type Columns = u64;
+
+/// Groups of rows which agree on some columns, excluding those
+/// groups which contain a single row
+type Partition = Vec<Vec<usize>>;
+
+/// The largest number of columns FDs can be discovered on
+pub const MAX_COLUMNS: usize = 63;
+
+/// Produce the indexes of all columns in a set
+fn indexes(columns: Columns) -> impl Iterator<Item = usize> {
+    (0..64).filter(move |i| columns & (1 << i) != 0)
+}
+
+/// The number of rows which would need to be removed for the columns
+/// defining a partition to be a key
+fn partition_error(partition: &Partition) -> usize {
+    partition.iter().map(|c| c.len() - 1).sum()
+}
+
+/// Partition rows by the values of a single column
+fn column_partition(relation: &Relation, column: usize) -> Partition {
+    let mut classes: IndexMap<&str, Vec<usize>> = IndexMap::new();
+    for (row, value) in relation.column(column).enumerate() {
+        classes.entry(value).or_default().push(row);
+    }
+
+    classes.into_values().filter(|c| c.len() > 1).collect()
+}
+
+/// Partition rows by the columns of two partitions together
+fn partition_product(left: &Partition, right: &Partition, rows: usize) -> Partition {
+    let mut classes = vec![None; rows];
+    for (i, class) in left.iter().enumerate() {
+        for &row in class {
+            classes[row] = Some(i);
+        }
+    }
+
+    let mut groups = vec![Vec::new(); left.len()];
+    let mut product = Vec::new();
+    for class in right {
+        for &row in class {
+            if let Some(i) = classes[row] {
+                groups[i].push(row);
+            }
+        }
+        for &row in class {
+            if let Some(i) = classes[row] {
+                if groups[i].len() > 1 {
+                    product.push(groups[i].split_off(0));
+                } else {
+                    groups[i].clear();
+                }
+            }
+        }
+    }
+
+    product
+}
+
+/// State of the search through the lattice of column sets
+struct Search<'a> {
+    relation: &'a Relation,
+    all: Columns,
+
+    /// Candidate right-hand sides for each column set
+    candidates: HashMap<Columns, Columns>,
+
+    /// Partitions for column sets in the current and previous level
+    partitions: HashMap<Columns, Partition>,
+
+    /// Minimal FDs found given by the left-hand side and right-hand column
+    fds: Vec<(Columns, usize)>,
+
+    /// Minimal keys found in the order they were discovered
+    keys: Vec<Columns>,
+}
+
+impl<'a> Search<'a> {
+    /// Find candidate right-hand sides for a set of columns which may
+    /// not be in the current level
+    fn candidates(&mut self, columns: Columns) -> Columns {
+        if let Some(&candidates) = self.candidates.get(&columns) {
+            return candidates;
+        }
+
+        let mut candidates = self.all;
+        for i in indexes(columns) {
+            candidates &= self.candidates(columns & !(1 << i));
+        }
+        self.candidates.insert(columns, candidates);
+        candidates
+    }
+
+    /// Find FDs whose left-hand side is a subset of each set of columns
+    fn compute_dependencies(&mut self, level: &[Columns]) {
+        for &columns in level {
+            self.candidates.remove(&columns);
+            self.candidates(columns);
+        }
+
+        for &columns in level {
+            let error = partition_error(&self.partitions[&columns]);
+            for i in indexes(columns & self.candidates[&columns]) {
+                let lhs = columns & !(1 << i);
+                if partition_error(&self.partitions[&lhs]) == error {
+                    self.fds.push((lhs, i));
+                    let candidates = self.candidates.get_mut(&columns).unwrap();
+                    *candidates &= !(1 << i) & columns;
+                }
+            }
+        }
+    }
+
+    /// Remove sets of columns which cannot produce further minimal FDs
+    fn prune(&mut self, level: Vec<Columns>) -> Vec<Columns> {
+        let mut remaining = Vec::new();
+        for columns in level {
+            let candidates = self.candidates[&columns];
+            if candidates == 0 {
+                continue;
+            }
+
+            // Keys determine every other column
+            if partition_error(&self.partitions[&columns]) == 0 {
+                for i in indexes(candidates & !columns) {
+                    let minimal = indexes(columns)
+                        .all(|j| self.candidates((columns | (1 << i)) & !(1 << j)) & (1 << i) != 0);
+                    if minimal {
+                        self.fds.push((columns, i));
+                    }
+                }
+                self.keys.push(columns);
+                continue;
+            }
+
+            remaining.push(columns);
+        }
+
+        remaining
+    }
+
+    /// Produce the next level from sets of columns sharing all but one column
+    fn generate_next_level(&mut self, level: &[Columns]) -> Vec<Columns> {
+        let current = level.iter().cloned().collect::<HashSet<_>>();
+        let mut blocks: IndexMap<Columns, Vec<Columns>> = IndexMap::new();
+        for &columns in level {
+            let highest = 63 - columns.leading_zeros();
+            blocks
+                .entry(columns & !(1 << highest))
+                .or_default()
+                .push(columns);
+        }
+
+        let mut next = Vec::new();
+        for block in blocks.values() {
+            for (i, &left) in block.iter().enumerate() {
+                for &right in &block[i + 1..] {
+                    let columns = left | right;
+                    if indexes(columns).all(|j| current.contains(&(columns & !(1 << j)))) {
+                        let partition = partition_product(
+                            &self.partitions[&left],
+                            &self.partitions[&right],
+                            self.relation.rows.len(),
+                        );
+                        self.partitions.insert(columns, partition);
+                        next.push(columns);
+                    }
+                }
+            }
+        }
+
+        // Only partitions of the previous level are needed
+        let next_set = next.iter().cloned().collect::<HashSet<_>>();
+        self.partitions
+            .retain(|columns, _| current.contains(columns) || next_set.contains(columns));
+        next.sort();
+        next
+    }
+}
+
+/// Dependencies discovered on a relation given by column indexes
+pub struct Discovered {
+    /// Minimal FDs given by their left-hand side and right-hand column
+    pub fds: Vec<(Vec<usize>, usize)>,
+
+    /// Minimal keys of the relation
+    pub keys: Vec<Vec<usize>>,
+}
+
+/// Discover all minimal FDs with a single column on the right-hand side
+/// which hold on a relation along with its minimal keys using TANE
+///
+/// Columns which have the same value in every row are not reported.
+pub fn discover_fds(relation: &Relation) -> Discovered {
+    let column_count = relation.columns.len();
+    assert!(column_count <= MAX_COLUMNS);
+
+    let all = (1 << column_count) - 1;
+    let mut search = Search {
+        relation,
+        all,
+        candidates: HashMap::new(),
+        partitions: HashMap::new(),
+        fds: Vec::new(),
+        keys: Vec::new(),
+    };
+    search.candidates.insert(0, all);
+
+    let rows = relation.rows.len();
+    let empty_partition = if rows > 1 {
+        vec![(0..rows).collect()]
+    } else {
+        Vec::new()
+    };
+    search.partitions.insert(0, empty_partition);
+
+    let mut level = Vec::new();
+    for column in 0..column_count {
+        search
+            .partitions
+            .insert(1 << column, column_partition(relation, column));
+        level.push(1 << column);
+    }
+
+    while !level.is_empty() {
+        search.compute_dependencies(&level);
+        level = search.prune(level);
+        level = search.generate_next_level(&level);
+    }
+
+    let fds = search
+        .fds
+        .into_iter()
+        .filter(|&(lhs, _)| lhs != 0)
+        .map(|(lhs, rhs)| (indexes(lhs).collect(), rhs))
+        .collect();
+    let keys = search
+        .keys
+        .into_iter()
+        .map(|k| indexes(k).collect())
+        .collect();
+    Discovered { fds, keys }
+}
+
+/// A column given by the index of its relation and its index in the relation
+type Attribute = (usize, usize);
+
+/// Produce the distinct non-empty values of a column in sorted order
+fn sorted_values(relation: &Relation, column: usize) -> Vec<&str> {
+    let mut values = relation
+        .column(column)
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<_>>();
+    values.sort_unstable();
+    values.dedup();
+    values
+}
+
+/// Discover unary INDs between columns of different relations by
+/// merging the sorted distinct values of all columns as in SPIDER
+fn unary_inds(relations: &[Relation]) -> Vec<(Attribute, Attribute)> {
+    let attributes = relations
+        .iter()
+        .enumerate()
+        .flat_map(|(r, relation)| (0..relation.columns.len()).map(move |c| (r, c)))
+        .collect::<Vec<Attribute>>();
+    let values = attributes
+        .iter()
+        .map(|&(r, c)| sorted_values(&relations[r], c))
+        .collect::<Vec<_>>();
+
+    // Columns with values start out included in every column of other relations
+    let mut referenced = attributes
+        .iter()
+        .zip(values.iter())
+        .map(|(&(r, _), v)| {
+            if v.is_empty() {
+                return HashSet::new();
+            }
+            (0..attributes.len())
+                .filter(|&j| attributes[j].0 != r)
+                .collect::<HashSet<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut heap = BinaryHeap::new();
+    for (i, v) in values.iter().enumerate() {
+        if let Some(&first) = v.first() {
+            heap.push(Reverse((first, i, 0)));
+        }
+    }
+
+    // Each value removes columns not containing it from the
+    // referenced columns of every column which does
+    while let Some(&Reverse((value, _, _))) = heap.peek() {
+        let mut containing = HashSet::new();
+        while let Some(&Reverse((next, i, position))) = heap.peek() {
+            if next != value {
+                break;
+            }
+            heap.pop();
+            containing.insert(i);
+            if let Some(&v) = values[i].get(position + 1) {
+                heap.push(Reverse((v, i, position + 1)));
+            }
+        }
+
+        for &i in &containing {
+            referenced[i].retain(|j| containing.contains(j));
+        }
+    }
+
+    let mut inds = Vec::new();
+    for (i, references) in referenced.iter().enumerate() {
+        for &j in references {
+            inds.push((attributes[i], attributes[j]));
+        }
+    }
+    inds.sort();
+    inds
+}
+
+/// An IND between two relations given by their indexes and pairs of
+/// left and right columns with left columns in increasing order
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct Inclusion {
+    left: usize,
+    right: usize,
+    columns: Vec<(usize, usize)>,
+}
+
+impl Inclusion {
+    /// Produce the INDs with one fewer pair of columns
+    fn generalizations(&self) -> impl Iterator<Item = Inclusion> + '_ {
+        (0..self.columns.len()).map(move |i| {
+            let mut columns = self.columns.clone();
+            columns.remove(i);
+            Inclusion {
+                left: self.left,
+                right: self.right,
+                columns,
+            }
+        })
+    }
+}
+
+/// Produce the combinations of values in some columns of each row,
+/// skipping rows where any of the values are empty
+fn projection<'a>(
+    relation: &'a Relation,
+    columns: Vec<usize>,
+) -> impl Iterator<Item = Vec<&'a str>> + 'a {
+    relation
+        .rows
+        .iter()
+        .map(move |row| columns.iter().map(|&c| row[c].as_str()).collect::<Vec<_>>())
+        .filter(|values| values.iter().all(|v| !v.is_empty()))
+}
+
+/// Check whether every combination of values on the left-hand side of
+/// an IND also appears on the right-hand side
+fn inclusion_holds(relations: &[Relation], ind: &Inclusion) -> bool {
+    let left = ind.columns.iter().map(|c| c.0).collect();
+    let right = ind.columns.iter().map(|c| c.1).collect();
+    let referenced = projection(&relations[ind.right], right).collect::<HashSet<_>>();
+    projection(&relations[ind.left], left).all(|values| referenced.contains(&values))
+}
+
+/// Generate candidate INDs with one more pair of columns from valid
+/// INDs which share all but their last pair as in MIND
+fn next_candidates(level: &[Inclusion]) -> Vec<Inclusion> {
+    let valid = level.iter().collect::<HashSet<_>>();
+    let mut blocks: IndexMap<_, Vec<&Inclusion>> = IndexMap::new();
+    for ind in level {
+        let prefix = &ind.columns[..ind.columns.len() - 1];
+        blocks
+            .entry((ind.left, ind.right, prefix))
+            .or_default()
+            .push(ind);
+    }
+
+    let mut candidates = Vec::new();
+    for block in blocks.values() {
+        for (i, first) in block.iter().enumerate() {
+            for second in &block[i + 1..] {
+                // Each column can only be used once on either side
+                let last = *second.columns.last().unwrap();
+                if first.columns.iter().any(|c| c.0 == last.0 || c.1 == last.1) {
+                    continue;
+                }
+
+                let mut columns = first.columns.clone();
+                columns.push(last);
+                let candidate = Inclusion {
+                    left: first.left,
+                    right: first.right,
+                    columns,
+                };
+                if candidate.generalizations().all(|g| valid.contains(&g)) {
+                    candidates.push(candidate);
+                }
+            }
+        }
+    }
+
+    candidates
+}
+
+/// Discover the maximal INDs which hold between columns of different
+/// relations, starting from unary INDs and adding a column at a time
+pub fn discover_inds(relations: &[Relation]) -> Vec<IND> {
+    let mut level = unary_inds(relations)
+        .into_iter()
+        .map(|((left, l), (right, r))| Inclusion {
+            left,
+            right,
+            columns: vec![(l, r)],
+        })
+        .collect::<Vec<_>>();
+
+    let mut maximal = Vec::new();
+    while !level.is_empty() {
+        info!("Testing INDs with {} columns", level[0].columns.len());
+        let mut next = next_candidates(&level);
+        next.retain(|candidate| inclusion_holds(relations, candidate));
+        next.sort();
+
+        let implied = next
+            .iter()
+            .flat_map(|ind| ind.generalizations())
+            .collect::<HashSet<_>>();
+        maximal.extend(level.into_iter().filter(|ind| !implied.contains(ind)));
+        level = next;
+    }
+
+    maximal.sort();
+    maximal
+        .into_iter()
+        .map(|ind| {
+            let left_relation = &relations[ind.left];
+            let right_relation = &relations[ind.right];
+            let lhs = ind
+                .columns
+                .iter()
+                .map(|c| left_relation.columns[c.0].parse().unwrap())
+                .collect::<Vec<FieldName>>();
+            let rhs = ind
+                .columns
+                .iter()
+                .map(|c| right_relation.columns[c.1].parse().unwrap())
+                .collect::<Vec<FieldName>>();
+            let permutation = permutation::sort(&lhs[..]);
+
+            IND {
+                left_table: left_relation.name.parse().unwrap(),
+                left_fields: permutation.apply_slice(&lhs[..]),
+                right_table: right_relation.name.parse().unwrap(),
+                right_fields: permutation.apply_slice(&rhs[..]),
+            }
+        })
+        .collect()
+}
+
+/// Discover FDs on relations and INDs between them to produce input
+/// with a table for each relation, using the smallest discovered key
+/// as the primary key
+pub fn discover_input(relations: &[Relation]) -> Input {
+    let mut discovered = Input {
+        tables: Vec::new(),
+        fds: Vec::new(),
+        inds: Vec::new(),
+        stats: Vec::new(),
+    };
+
+    for relation in relations {
+        info!("Discovering FDs for {}", relation.name);
+        let Discovered { fds, keys } = discover_fds(relation);
+        let key = keys
+            .iter()
+            .min_by_key(|k| k.len())
+            .cloned()
+            .unwrap_or_default();
+
+        let mut table = Table {
+            name: relation.name.parse().unwrap(),
+            fields: IndexMap::new(),
+            ..Default::default()
+        };
+        for (i, column) in relation.columns.iter().enumerate() {
+            table.fields.insert(
+                column.parse().unwrap(),
+                Field {
+                    name: column.parse().unwrap(),
+                    key: key.contains(&i),
+                    cardinality: None,
+                    max_length: None,
+                    field_type: None,
+                },
+            );
+        }
+        discovered.tables.push(table);
+
+        for (lhs, rhs) in fds {
+            discovered.fds.push((
+                relation.name.clone(),
+                lhs.iter().map(|&i| relation.columns[i].clone()).collect(),
+                vec![relation.columns[rhs].clone()],
+                1.0,
+            ));
+        }
+        discovered
+            .stats
+            .push((relation.name.clone(), None, relation.rows.len(), None));
+    }
+
+    info!("Discovering INDs");
+    for ind in discover_inds(relations) {
+        discovered.inds.push((
+            ind.left_table.to_string(),
+            ind.left_fields.iter().map(|f| f.to_string()).collect(),
+            ind.right_table.to_string(),
+            ind.right_fields.iter().map(|f| f.to_string()).collect(),
+        ));
+    }
+
+    discovered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: Vec<&str>) -> Vec<String> {
+        values.into_iter().map(|v| v.to_string()).collect()
+    }
+
+    fn relation() -> Relation {
+        Relation {
+            name: "bids".to_string(),
+            columns: strings(vec!["id", "user_id", "nickname", "item_id"]),
+            rows: vec![
+                strings(vec!["1", "1", "alice", "1"]),
+                strings(vec!["2", "1", "alice", "2"]),
+                strings(vec!["3", "2", "bob", "1"]),
+                strings(vec!["4", "3", "bob", "2"]),
+            ],
+        }
+    }
+
+    fn users() -> Relation {
+        Relation {
+            name: "users".to_string(),
+            columns: strings(vec!["id", "name", "rating"]),
+            rows: vec![
+                strings(vec!["1", "alice", ""]),
+                strings(vec!["2", "bob", "4"]),
+                strings(vec!["3", "bob", "3"]),
+                strings(vec!["4", "carol", "4"]),
+            ],
+        }
+    }
+
+    #[test]
+    fn discover_minimal_fds() {
+        let Discovered { mut fds, mut keys } = discover_fds(&relation());
+        fds.sort();
+        keys.sort();
+
+        assert_eq!(
+            fds,
+            vec![
+                (vec![0], 1),
+                (vec![0], 2),
+                (vec![0], 3),
+                (vec![1], 2),
+                (vec![1, 3], 0),
+                (vec![2, 3], 0),
+                (vec![2, 3], 1),
+            ]
+        );
+        assert_eq!(keys, vec![vec![0], vec![1, 3], vec![2, 3]]);
+    }
+
+    #[test]
+    fn discover_input_key() {
+        let discovered = discover_input(&[relation()]);
+        assert_eq!(
+            discovered.tables[0].to_string(),
+            "bids(*id, item_id, nickname, user_id)"
+        );
+        assert_eq!(discovered.fds.len(), 7);
+        assert_eq!(discovered.stats, vec![("bids".to_string(), None, 4, None)]);
+    }
+
+    #[test]
+    fn discover_unary_inds() {
+        let inds = unary_inds(&[relation(), users()]);
+        assert_eq!(
+            inds,
+            vec![
+                ((0, 0), (1, 0)),
+                ((0, 1), (1, 0)),
+                ((0, 2), (1, 1)),
+                ((0, 3), (1, 0)),
+                ((1, 0), (0, 0)),
+                ((1, 2), (0, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn discover_nary_inds() {
+        let inds = discover_inds(&[relation(), users()]);
+        assert_eq!(
+            inds,
+            vec![
+                IND {
+                    left_table: "bids".parse().unwrap(),
+                    left_fields: vec!["id".parse().unwrap()],
+                    right_table: "users".parse().unwrap(),
+                    right_fields: vec!["id".parse().unwrap()],
+                },
+                IND {
+                    left_table: "bids".parse().unwrap(),
+                    left_fields: vec!["nickname".parse().unwrap(), "user_id".parse().unwrap()],
+                    right_table: "users".parse().unwrap(),
+                    right_fields: vec!["name".parse().unwrap(), "id".parse().unwrap()],
+                },
+                IND {
+                    left_table: "bids".parse().unwrap(),
+                    left_fields: vec!["item_id".parse().unwrap()],
+                    right_table: "users".parse().unwrap(),
+                    right_fields: vec!["id".parse().unwrap()],
+                },
+                IND {
+                    left_table: "users".parse().unwrap(),
+                    left_fields: vec!["id".parse().unwrap()],
+                    right_table: "bids".parse().unwrap(),
+                    right_fields: vec!["id".parse().unwrap()],
+                },
+                IND {
+                    left_table: "users".parse().unwrap(),
+                    left_fields: vec!["rating".parse().unwrap()],
+                    right_table: "bids".parse().unwrap(),
+                    right_fields: vec!["id".parse().unwrap()],
+                },
+            ]
+        );
+    }
+}
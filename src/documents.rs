@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+use serde::de::Error;
+use serde_json::{Map, Value};
+
+use crate::data::Relation;
+use crate::input::Input;
+use crate::model::{Field, FieldType, Table};
+
+/// The kind of values seen in a column, used to infer its type
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Unknown,
+    Boolean,
+    Integer,
+    BigInt,
+    Double,
+    Text,
+    Timestamp,
+    Json,
+}
+
+impl Kind {
+    /// Find a kind which can hold values of both kinds
+    fn merge(self, other: Kind) -> Kind {
+        match (self, other) {
+            (Kind::Unknown, kind) | (kind, Kind::Unknown) => kind,
+            (a, b) if a == b => a,
+            (Kind::Integer, Kind::BigInt) | (Kind::BigInt, Kind::Integer) => Kind::BigInt,
+            (Kind::Integer, Kind::Double)
+            | (Kind::Double, Kind::Integer)
+            | (Kind::BigInt, Kind::Double)
+            | (Kind::Double, Kind::BigInt) => Kind::Double,
+            _ => Kind::Text,
+        }
+    }
+
+    fn field_type(self) -> Option<FieldType> {
+        match self {
+            Kind::Unknown => None,
+            Kind::Boolean => Some(FieldType::Boolean),
+            Kind::Integer => Some(FieldType::Integer),
+            Kind::BigInt => Some(FieldType::BigInt),
+            Kind::Double => Some(FieldType::Double),
+            Kind::Text => Some(FieldType::Text),
+            Kind::Timestamp => Some(FieldType::Timestamp),
+            Kind::Json => Some(FieldType::Json),
+        }
+    }
+}
+
+/// Convert a scalar value to text along with its kind, treating
+/// MongoDB extended JSON such as `{"$oid": ...}` as a scalar
+fn scalar(value: &Value) -> Option<(String, Kind)> {
+    match *value {
+        Value::Null => Some((String::new(), Kind::Unknown)),
+        Value::Bool(b) => Some((b.to_string(), Kind::Boolean)),
+        Value::Number(ref n) => {
+            let kind = match n.as_i64() {
+                Some(i) if (i64::from(i32::MIN)..=i64::from(i32::MAX)).contains(&i) => {
+                    Kind::Integer
+                }
+                Some(_) => Kind::BigInt,
+                None => Kind::Double,
+            };
+            Some((n.to_string(), kind))
+        }
+        Value::String(ref s) => Some((s.clone(), Kind::Text)),
+        Value::Object(ref object) if object.len() == 1 => {
+            let (key, inner) = object.iter().next().unwrap();
+            if !key.starts_with('$') {
+                return None;
+            }
+            let (text, kind) = scalar(inner)?;
+            let kind = match key.as_str() {
+                "$date" => Kind::Timestamp,
+                "$numberInt" => Kind::Integer,
+                "$numberLong" => Kind::BigInt,
+                "$numberDouble" | "$numberDecimal" => Kind::Double,
+                _ => kind,
+            };
+            Some((text, kind))
+        }
+        _ => None,
+    }
+}
+
+/// Convert a key of a document into a valid field or table name
+fn identifier(key: &str) -> String {
+    let mut name = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// A table being built from the documents of a collection
+struct Builder {
+    /// Columns forming the key of the table
+    key: Vec<String>,
+
+    /// All columns in order along with the kind of their values
+    columns: IndexMap<String, Kind>,
+
+    /// Values of each row by column name
+    rows: Vec<HashMap<String, String>>,
+
+    /// The table this table is nested in along with its key
+    parent: Option<(String, Vec<String>)>,
+}
+
+impl Builder {
+    fn new(key: Vec<String>, parent: Option<(String, Vec<String>)>) -> Builder {
+        Builder {
+            columns: key.iter().map(|k| (k.clone(), Kind::Unknown)).collect(),
+            key,
+            rows: Vec::new(),
+            parent,
+        }
+    }
+
+    /// Find the column used for a field, avoiding key columns
+    fn column(&self, name: String) -> String {
+        if self.key.contains(&name) {
+            name + "_"
+        } else {
+            name
+        }
+    }
+
+    /// Record a value of a column in a row
+    fn set(&mut self, row: &mut HashMap<String, String>, column: String, text: String, kind: Kind) {
+        let seen = self.columns.entry(column.clone()).or_insert(Kind::Unknown);
+        *seen = seen.merge(kind);
+        row.insert(column, text);
+    }
+}
+
+/// Flattens documents into a table for the collection and each array
+struct Flattener {
+    tables: IndexMap<String, Builder>,
+}
+
+impl Flattener {
+    /// Add the fields of an object to a row of a table, flattening nested
+    /// objects into columns with a prefix and arrays into child tables
+    fn add_object(
+        &mut self,
+        table: &str,
+        prefix: &str,
+        object: &Map<String, Value>,
+        row: &mut HashMap<String, String>,
+    ) {
+        for (key, value) in object {
+            // Scalar identifiers of documents are already used as the key
+            let is_id = prefix.is_empty() && key == "_id" && self.tables[table].parent.is_none();
+            if is_id && scalar(value).is_some() {
+                continue;
+            }
+
+            let name = format!("{}{}", prefix, identifier(key));
+            if let Some((text, kind)) = scalar(value) {
+                let builder = self.tables.get_mut(table).unwrap();
+                let column = builder.column(name);
+                builder.set(row, column, text, kind);
+                continue;
+            }
+
+            match *value {
+                Value::Object(ref nested) => {
+                    self.add_object(table, &format!("{}_", name), nested, row);
+                }
+                Value::Array(ref elements) => self.add_array(table, &name, elements, row),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    /// Add the elements of an array in a row of a table to a child table
+    /// keyed by the key of the row and the position in the array
+    fn add_array(
+        &mut self,
+        table: &str,
+        name: &str,
+        elements: &[Value],
+        row: &HashMap<String, String>,
+    ) {
+        let child = format!("{}_{}", table, name);
+        let parent_key = self.tables[table].key.clone();
+        let mut key = parent_key
+            .iter()
+            .map(|k| {
+                if k == "_id" || k == "position" {
+                    format!("{}_{}", table, k.trim_start_matches('_'))
+                } else {
+                    k.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+        key.push("position".to_string());
+        if !self.tables.contains_key(&child) {
+            let builder = Builder::new(key.clone(), Some((table.to_string(), parent_key.clone())));
+            self.tables.insert(child.clone(), builder);
+        }
+
+        for (position, element) in elements.iter().enumerate() {
+            let mut child_row = HashMap::new();
+            for (column, parent_column) in key.iter().zip(parent_key.iter()) {
+                let kind = self.tables[table].columns[parent_column];
+                let builder = self.tables.get_mut(&child).unwrap();
+                builder.set(
+                    &mut child_row,
+                    column.clone(),
+                    row[parent_column].clone(),
+                    kind,
+                );
+            }
+            let builder = self.tables.get_mut(&child).unwrap();
+            builder.set(
+                &mut child_row,
+                "position".to_string(),
+                position.to_string(),
+                Kind::Integer,
+            );
+
+            if let Some((text, kind)) = scalar(element) {
+                let builder = self.tables.get_mut(&child).unwrap();
+                let column = builder.column(name.to_string());
+                builder.set(&mut child_row, column, text, kind);
+            } else if let Value::Object(ref object) = *element {
+                self.add_object(&child, "", object, &mut child_row);
+            } else {
+                // Arrays directly within arrays are kept as JSON
+                let builder = self.tables.get_mut(&child).unwrap();
+                let column = builder.column(name.to_string());
+                builder.set(&mut child_row, column, element.to_string(), Kind::Json);
+            }
+
+            self.tables.get_mut(&child).unwrap().rows.push(child_row);
+        }
+    }
+}
+
+/// Read a collection of JSON documents, one per line, as a table for
+/// the collection keyed by `_id` and a child table for each array keyed
+/// by the key of its parent and the position in the array
+///
+/// Documents without a scalar `_id` are identified by their position.
+pub fn parse(name: &str, input: &str) -> Result<Input, serde_json::Error> {
+    let root = identifier(name);
+    let mut flattener = Flattener {
+        tables: IndexMap::new(),
+    };
+    flattener
+        .tables
+        .insert(root.clone(), Builder::new(vec!["_id".to_string()], None));
+
+    let documents = serde_json::Deserializer::from_str(input).into_iter::<Value>();
+    for (position, value) in documents.enumerate() {
+        let document = match value? {
+            Value::Object(document) => document,
+            other => {
+                return Err(serde_json::Error::custom(format!(
+                    "expected a document but found {}",
+                    other
+                )))
+            }
+        };
+
+        let (id, kind) = document
+            .get("_id")
+            .and_then(scalar)
+            .unwrap_or_else(|| (position.to_string(), Kind::Integer));
+        let mut row = HashMap::new();
+        let builder = flattener.tables.get_mut(&root).unwrap();
+        builder.set(&mut row, "_id".to_string(), id, kind);
+        flattener.add_object(&root, "", &document, &mut row);
+        flattener.tables.get_mut(&root).unwrap().rows.push(row);
+    }
+
+    let mut parsed = Input {
+        tables: Vec::new(),
+        fds: Vec::new(),
+        inds: Vec::new(),
+        stats: Vec::new(),
+    };
+
+    for (name, builder) in flattener.tables {
+        let columns = builder.columns.keys().cloned().collect::<Vec<_>>();
+        let rows = builder
+            .rows
+            .iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .map(|c| row.get(c).cloned().unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+        let relation = Relation {
+            name: name.clone(),
+            columns,
+            rows,
+        };
+        parsed.stats.extend(relation.stats());
+
+        let mut table = Table {
+            name: name.parse().unwrap(),
+            fields: IndexMap::new(),
+            ..Default::default()
+        };
+        for (column, kind) in &builder.columns {
+            table.fields.insert(
+                column.parse().unwrap(),
+                Field {
+                    name: column.parse().unwrap(),
+                    key: builder.key.contains(column),
+                    cardinality: None,
+                    max_length: None,
+                    field_type: kind.field_type(),
+                },
+            );
+        }
+        table.add_pk_fd();
+        parsed.tables.push(table);
+
+        // Each nested row belongs to a row of its parent
+        if let Some((parent, parent_key)) = builder.parent {
+            let child_key = builder.key[..parent_key.len()].to_vec();
+            parsed.inds.push((name, child_key, parent, parent_key));
+        }
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::FieldName;
+
+    #[test]
+    fn parse_nested_documents() {
+        let parsed = parse(
+            "users",
+            "{\"_id\": {\"$oid\": \"a1\"}, \"name\": \"alice\", \"address\": {\"city\": \"Paris\"}, \
+              \"orders\": [{\"sku\": \"x\", \"items\": [{\"qty\": 1}]}], \"tags\": [\"new\"]}\n\
+             {\"_id\": {\"$oid\": \"b2\"}, \"name\": \"bob\", \"tags\": []}\n",
+        )
+        .unwrap();
+
+        let tables = parsed
+            .tables
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tables,
+            vec![
+                "users(*_id, address_city, name)",
+                "users_orders(*position, *users_id, sku)",
+                "users_orders_items(*position, *users_id, *users_orders_position, qty)",
+                "users_tags(*position, *users_id, tags)",
+            ]
+        );
+        assert_eq!(
+            parsed.tables[1].fields[&FieldName::from("position")].field_type,
+            Some(FieldType::Integer)
+        );
+
+        assert_eq!(
+            parsed.inds,
+            vec![
+                (
+                    "users_orders".to_string(),
+                    vec!["users_id".to_string()],
+                    "users".to_string(),
+                    vec!["_id".to_string()]
+                ),
+                (
+                    "users_orders_items".to_string(),
+                    vec!["users_id".to_string(), "users_orders_position".to_string()],
+                    "users_orders".to_string(),
+                    vec!["users_id".to_string(), "position".to_string()]
+                ),
+                (
+                    "users_tags".to_string(),
+                    vec!["users_id".to_string()],
+                    "users".to_string(),
+                    vec!["_id".to_string()]
+                ),
+            ]
+        );
+        assert!(parsed
+            .stats
+            .contains(&("users".to_string(), Some("_id".to_string()), 2, Some(2))));
+    }
+
+    #[test]
+    fn parse_without_ids() {
+        let parsed = parse("events", "{\"kind\": 1}\n{\"kind\": 2.5}\n").unwrap();
+        assert_eq!(parsed.tables[0].to_string(), "events(*_id, kind)");
+        assert_eq!(
+            parsed.tables[0].fields[1].field_type,
+            Some(FieldType::Double)
+        );
+        assert!(parse("events", "[1, 2]\n").is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::HashSet;
+
+use indexmap::IndexMap;
+
+use crate::input::{self, Entry, Input, ParseError, Section};
+
+/// A comment from an input file
+#[derive(Clone, Debug, PartialEq)]
+struct Comment {
+    /// The text of the comment including the leading `#`
+    text: String,
+
+    /// Whether the comment followed an entry on the same line
+    trailing: bool,
+}
+
+/// The sections of an input file in the order they appear without headers
+const SECTIONS: [Section; 4] = [Section::Tables, Section::FDs, Section::INDs, Section::Stats];
+
+/// Find the entries defined by some lines in a section of an input file
+fn line_entries(lines: &str, section: Section) -> Option<Vec<Entry>> {
+    input::parse(&format!("[{}]\n{}", section.header(), lines))
+        .ok()
+        .map(|parsed| input::entries(&parsed))
+        .filter(|entries| !entries.is_empty())
+}
+
+/// Split a line of an input file into its content and any comment
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    match line.find('#') {
+        Some(index) => (&line[..index], Some(&line[index..])),
+        None => (line, None),
+    }
+}
+
+/// Find the section of each line of an input file along with the
+/// entries defined by the lines ending there
+fn line_sections(source: &str) -> Vec<(Section, Option<Vec<Entry>>)> {
+    let mut sections = Vec::new();
+    let mut buffer = String::new();
+    let mut section = Section::Tables;
+    for line in source.lines() {
+        let content = split_comment(line).0.trim();
+        if content.is_empty() {
+            sections.push((section, None));
+            continue;
+        }
+        if let Some(header) = Section::of_header(content) {
+            section = header;
+            sections.push((section, None));
+            continue;
+        }
+
+        // Tables may span several lines so wait until they are complete,
+        // and without headers a line outside the current section starts
+        // one of the sections which follow it
+        buffer.push_str(content);
+        buffer.push('\n');
+        let found = SECTIONS
+            .iter()
+            .skip_while(|&&s| s != section)
+            .find_map(|&s| line_entries(&buffer, s).map(|entries| (s, entries)));
+        match found {
+            Some((line_section, entries)) => {
+                section = line_section;
+                sections.push((section, Some(entries)));
+                buffer.clear();
+            }
+            None => sections.push((section, None)),
+        }
+    }
+
+    sections
+}
+
+/// Format the contents of an input file in canonical form, keeping
+/// comments with the entries they annotate
+pub fn format(source: &str) -> Result<String, ParseError> {
+    let parsed = input::parse(source)?;
+
+    // Comments before the first blank line describe the whole file and
+    // comments after the last entry are kept at the end
+    let mut header = Vec::new();
+    let mut comments: IndexMap<Entry, Vec<Comment>> = IndexMap::new();
+    let mut pending = Vec::new();
+    let mut seen_entry = false;
+    let mut in_entry = false;
+    for (line, (_, entries)) in source.lines().zip(line_sections(source)) {
+        let (content, comment) = split_comment(line);
+        let content = content.trim();
+        if let Some(text) = comment {
+            pending.push(Comment {
+                text: text.trim_end().to_string(),
+                trailing: !content.is_empty(),
+            });
+        }
+
+        if let Some(entries) = entries {
+            seen_entry = true;
+            in_entry = false;
+            comments
+                .entry(entries[0].clone())
+                .or_default()
+                .append(&mut pending);
+        } else if content.is_empty() {
+            if comment.is_none() && !seen_entry && !in_entry {
+                header.append(&mut pending);
+            }
+        } else if Section::of_header(content).is_none() {
+            in_entry = true;
+        }
+    }
+
+    let mut output = String::new();
+    for comment in &header {
+        output.push_str(&comment.text);
+        output.push('\n');
+    }
+
+    for (section, lines) in input::canonical_lines(&parsed) {
+        if lines.is_empty() && section != Section::Tables {
+            continue;
+        }
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&format!("[{}]\n", section.header()));
+
+        for (entries, line) in lines {
+            let line_comments = entries
+                .iter()
+                .flat_map(|e| comments.shift_remove(e).unwrap_or_default())
+                .collect::<Vec<_>>();
+
+            // Only one comment can stay at the end of the line
+            let trailing = line_comments.iter().position(|c| c.trailing);
+            for (i, comment) in line_comments.iter().enumerate() {
+                if Some(i) != trailing {
+                    output.push_str(&comment.text);
+                    output.push('\n');
+                }
+            }
+            output.push_str(&line);
+            if let Some(i) = trailing {
+                output.push_str("  ");
+                output.push_str(&line_comments[i].text);
+            }
+            output.push('\n');
+        }
+    }
+
+    // Keep comments on entries which were removed as duplicates
+    let footer = comments
+        .into_iter()
+        .flat_map(|(_, c)| c)
+        .chain(pending)
+        .collect::<Vec<_>>();
+    if !footer.is_empty() {
+        output.push('\n');
+        for comment in footer {
+            output.push_str(&comment.text);
+            output.push('\n');
+        }
+    }
+
+    Ok(output)
+}
+
+/// Merge statistics into an input file, replacing the lines for existing
+/// statistics and leaving the rest of the file unchanged
+pub fn merge_stats(source: &str, stats: &Input) -> Result<String, ParseError> {
+    input::parse(source)?;
+
+    let mut new_lines = input::canonical_lines(stats)
+        .into_iter()
+        .filter(|(section, _)| *section == Section::Stats)
+        .flat_map(|(_, lines)| lines)
+        .map(|(entries, line)| (entries[0].clone(), line))
+        .collect::<IndexMap<_, _>>();
+
+    // Keep the indentation and any comment on replaced lines
+    let mut lines = source
+        .split_inclusive('\n')
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>();
+    let mut replaced = HashSet::new();
+    let mut last_stats = None;
+    for (i, (section, entries)) in line_sections(source).into_iter().enumerate() {
+        let entry = match entries {
+            Some(ref entries) if section == Section::Stats => &entries[0],
+            _ => continue,
+        };
+        last_stats = Some(i);
+        if let Some(new_line) = new_lines.get(entry) {
+            let line = &lines[i];
+            let content = split_comment(line).0.trim_end();
+            let indent = content.len() - content.trim_start().len();
+            lines[i] = format!("{}{}{}", &line[..indent], new_line, &line[content.len()..]);
+            replaced.insert(entry.clone());
+        }
+    }
+    new_lines.retain(|entry, _| !replaced.contains(entry));
+    if new_lines.is_empty() {
+        return Ok(lines.concat());
+    }
+
+    // Add other statistics after the last ones in the file or in a new section
+    let mut added = new_lines.values().map(|line| format!("{}\n", line));
+    match last_stats {
+        Some(i) => {
+            if !lines[i].ends_with('\n') {
+                lines[i].push('\n');
+            }
+            lines.splice(i + 1..i + 1, added);
+        }
+        None => {
+            if let Some(last) = lines.last_mut() {
+                if !last.ends_with('\n') {
+                    last.push('\n');
+                }
+                last.push('\n');
+            }
+            lines.push(format!("[{}]\n", Section::Stats.header()));
+            lines.extend(&mut added);
+        }
+    }
+
+    Ok(lines.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_canonical() {
+        let source = "# Mined schema\n\n\
+                      quux(*corge, grault)\n\
+                      foo( *bar,baz,  # the key\n  qux)\n\n\
+                      foo bar->qux\n\
+                      # why this FD was kept\n\
+                      foo bar -> baz  # verified\n\n\
+                      foo bar, baz <= quux corge, grault\n\
+                      foo baz <= quux grault  # subsumed\n\
+                      foo bar <= quux corge\n\
+                      quux corge <= foo bar\n\
+                      # duplicate\n\
+                      quux corge <= foo bar\n\
+                      \n\
+                      # end of file\n";
+        let formatted = format(source).unwrap();
+        assert_eq!(
+            formatted,
+            "# Mined schema\n\n\
+             [tables]\n\
+             foo(*bar, baz, qux)  # the key\n\
+             quux(*corge, grault)\n\n\
+             [fds]\n\
+             # why this FD was kept\n\
+             foo bar -> baz, qux  # verified\n\n\
+             [inds]\n\
+             foo bar, baz <= quux corge, grault\n\
+             # duplicate\n\
+             quux corge <= foo bar\n\n\
+             # subsumed\n\
+             # end of file\n"
+        );
+        assert_eq!(format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn format_headed_sections() {
+        let source = "[stats]\nfoo 10  # rows\n\n\
+                      [tables]\nfoo(*bar, baz)\n\n\
+                      [fds]\n# from the data\nfoo baz -> bar\n";
+        assert_eq!(
+            format(source).unwrap(),
+            "[tables]\nfoo(*bar, baz)\n\n\
+             [fds]\n# from the data\nfoo baz -> bar\n\n\
+             [stats]\nfoo 10  # rows\n"
+        );
+    }
+
+    #[test]
+    fn merge_stats_replaces_lines() {
+        let source = "# my schema\n\n\
+                      quux(*corge)\n\
+                      foo(*bar, baz)\n\n\
+                      [stats]\n\
+                      foo 5  # rows\n\
+                      quux corge 3\n\n\
+                      [fds]\n\
+                      foo baz -> bar\n";
+        let stats = Input {
+            tables: Vec::new(),
+            fds: Vec::new(),
+            inds: Vec::new(),
+            stats: vec![
+                ("foo".to_string(), None, 10, None),
+                ("foo".to_string(), Some("baz".to_string()), 4, Some(8)),
+            ],
+        };
+        assert_eq!(
+            merge_stats(source, &stats).unwrap(),
+            "# my schema\n\n\
+             quux(*corge)\n\
+             foo(*bar, baz)\n\n\
+             [stats]\n\
+             foo 10  # rows\n\
+             quux corge 3\n\
+             foo baz 4 8\n\n\
+             [fds]\n\
+             foo baz -> bar\n"
+        );
+    }
+
+    #[test]
+    fn merge_stats_new_section() {
+        let source = "# my schema\nquux(*corge)\nfoo(*bar, baz)";
+        let stats = Input {
+            tables: Vec::new(),
+            fds: Vec::new(),
+            inds: Vec::new(),
+            stats: vec![("foo".to_string(), None, 10, None)],
+        };
+        assert_eq!(
+            merge_stats(source, &stats).unwrap(),
+            "# my schema\nquux(*corge)\nfoo(*bar, baz)\n\n[stats]\nfoo 10\n"
+        );
+    }
+}
@@ -1,24 +1,704 @@
 extern crate peg;
 
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt;
 use std::str;
 
+use float_ord::FloatOrd;
 use indexmap::IndexMap;
+use itertools::Itertools;
 
-use super::model::{Field, Table};
+use super::dependencies::{minimal_cover, IND};
+use super::model::{Field, Schema, Table};
+
+/// The dependencies and statistics parsed from an input file
+pub struct Input {
+    /// Tables in the order they were defined
+    pub tables: Vec<Table>,
+
+    /// Functional dependencies as a table name, left and right-hand
+    /// sides, and the confidence the dependency holds with
+    pub fds: Vec<(String, Vec<String>, Vec<String>, f32)>,
+
+    /// Inclusion dependencies as a table name and fields for each side
+    pub inds: Vec<(String, Vec<String>, String, Vec<String>)>,
+
+    /// Statistics on tables (with no field) and fields
+    pub stats: Vec<(String, Option<String>, usize, Option<usize>)>,
+}
+
+impl Input {
+    /// Build a `Schema` from the parsed input, skipping any dependencies or
+    /// statistics which reference missing tables or fields, including FDs
+    /// with missing fields on the LHS, and dropping missing fields from the
+    /// RHS of FDs (see `validate`)
+    pub fn into_schema(self) -> Schema {
+        let mut schema = Schema {
+            ..Default::default()
+        };
+
+        // Build a HashMap of parsed Tables keeping the first definition
+        for table in self.tables {
+            if !schema.tables.contains_key(&table.name) {
+                schema.tables.insert(table.name.clone(), table);
+            }
+        }
+
+        // Copy frequencies to the tables and fields
+        for freq in self.stats {
+            let table = match schema.tables.get_mut(freq.0.as_str()) {
+                Some(table) => table,
+                None => continue,
+            };
+            match freq.1 {
+                Some(field_name) => {
+                    if let Some(field) = table.fields.get_mut(field_name.as_str()) {
+                        field.cardinality = Some(freq.2);
+                        field.max_length = freq.3;
+                    }
+                }
+                None => table.row_count = Some(freq.2),
+            }
+        }
+
+        // Add the FDs to each table
+        info!("Adding FDs");
+        for fd in self.fds {
+            let table = match schema.tables.get_mut(fd.0.as_str()) {
+                Some(table) => table,
+                None => continue,
+            };
+            let rhs =
+                fd.2.iter()
+                    .filter(|f| table.fields.contains_key(f.as_str()))
+                    .map(|s| s.parse().unwrap())
+                    .collect::<Vec<_>>();
+            if rhs.is_empty() || !fd.1.iter().all(|f| table.fields.contains_key(f.as_str())) {
+                continue;
+            }
+
+            table.add_approximate_fd(
+                fd.1.iter().map(|s| s.parse().unwrap()).collect::<Vec<_>>(),
+                rhs,
+                fd.3,
+            );
+        }
+
+        // Create a HashMap of INDs from the parsed data
+        info!("Adding INDs");
+        for ind in self.inds {
+            let valid = ind.1.len() == ind.3.len()
+                && [(&ind.0, &ind.1), (&ind.2, &ind.3)]
+                    .iter()
+                    .all(|&(table, fields)| match schema.tables.get(table.as_str()) {
+                        Some(table) => fields.iter().all(|f| table.fields.contains_key(f.as_str())),
+                        None => false,
+                    });
+            if !valid {
+                continue;
+            }
+
+            let lhs = ind.1.iter().map(|s| s.parse().unwrap()).collect::<Vec<_>>();
+            let permutation = permutation::sort(&lhs[..]);
+            let rhs = ind.3.iter().map(|s| s.parse().unwrap()).collect::<Vec<_>>();
+
+            let new_ind = IND {
+                left_table: ind.0.parse().unwrap(),
+                left_fields: permutation.apply_slice(&lhs[..]),
+                right_table: ind.2.parse().unwrap(),
+                right_fields: permutation.apply_slice(&rhs[..]),
+            };
+            schema.add_ind(new_ind);
+        }
+
+        schema
+    }
+}
+
+impl Input {
+    /// Produce the tables, dependencies and statistics of a `Schema`
+    pub fn from_schema(schema: &Schema) -> Input {
+        let mut input = Input {
+            tables: Vec::new(),
+            fds: Vec::new(),
+            inds: Vec::new(),
+            stats: Vec::new(),
+        };
+
+        for table in schema.tables.values().sorted_by_key(|t| t.name.clone()) {
+            // FDs with the same LHS are merged when written so this
+            // gives a canonical cover instead of the closed FDs
+            let name = table.name.to_string();
+            for fd in minimal_cover(table.all_fds()) {
+                input.fds.push((
+                    name.clone(),
+                    fd.lhs.iter().map(|f| f.to_string()).collect(),
+                    fd.rhs.iter().map(|f| f.to_string()).collect(),
+                    fd.confidence,
+                ));
+            }
+
+            if let Some(row_count) = table.row_count {
+                input.stats.push((name.clone(), None, row_count, None));
+            }
+            for field in table.fields.values() {
+                if let Some(cardinality) = field.cardinality {
+                    input.stats.push((
+                        name.clone(),
+                        Some(field.name.to_string()),
+                        cardinality,
+                        field.max_length,
+                    ));
+                }
+            }
+
+            input.tables.push(table.clone());
+        }
+
+        for ind in schema.inds.values().flatten() {
+            input.inds.push((
+                ind.left_table.to_string(),
+                ind.left_fields.iter().map(|f| f.to_string()).collect(),
+                ind.right_table.to_string(),
+                ind.right_fields.iter().map(|f| f.to_string()).collect(),
+            ));
+        }
+
+        input
+    }
+}
+
+/// The contents of a single section of an input file
+pub enum Block {
+    Tables(Vec<Table>),
+    FDs(Vec<(String, Vec<String>, Vec<String>, f32)>),
+    INDs(Vec<(String, Vec<String>, String, Vec<String>)>),
+    Stats(Vec<(String, Option<String>, usize, Option<usize>)>),
+}
+
+/// The sections of an input file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    Tables,
+    FDs,
+    INDs,
+    Stats,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Section::Tables => "tables",
+            Section::FDs => "functional dependencies",
+            Section::INDs => "inclusion dependencies",
+            Section::Stats => "statistics",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Section {
+    /// The name used in the header of this section
+    pub fn header(&self) -> &'static str {
+        match *self {
+            Section::Tables => "tables",
+            Section::FDs => "fds",
+            Section::INDs => "inds",
+            Section::Stats => "stats",
+        }
+    }
+
+    /// Find the section named by a header such as `[fds]`
+    pub fn of_header(line: &str) -> Option<Section> {
+        let line = line.split('#').next().unwrap().trim();
+        if !line.starts_with('[') || !line.ends_with(']') {
+            return None;
+        }
+
+        match line[1..line.len() - 1].trim() {
+            "tables" => Some(Section::Tables),
+            "fds" => Some(Section::FDs),
+            "inds" => Some(Section::INDs),
+            "stats" => Some(Section::Stats),
+            _ => None,
+        }
+    }
+}
+
+/// The position where the grammar started parsing a line along with the
+/// section of the line, or `None` for a section header
+type LineStart = (usize, Option<Section>);
+
+/// An error produced when an input file does not match the grammar
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    /// The section being parsed when the error occurred, which is `None`
+    /// when the error is in a section header
+    pub section: Option<Section>,
+
+    /// The line where the error occurred (1-indexed)
+    pub line: usize,
+
+    /// The column where the error occurred (1-indexed)
+    pub column: usize,
+
+    /// The text found at the location of the error
+    pub found: String,
+
+    /// The tokens which would have been accepted
+    pub expected: Vec<String>,
+
+    /// The full text of the line containing the error
+    pub source_line: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unexpected {}", self.found)?;
+        if let Some(section) = self.section {
+            write!(f, " in {} section", section)?;
+        }
+        writeln!(
+            f,
+            ", expected {}",
+            if self.expected.len() == 1 {
+                self.expected[0].clone()
+            } else {
+                format!("one of {}", self.expected.join(", "))
+            }
+        )?;
+
+        // Render the offending line with a marker under the error
+        let gutter = " ".repeat(self.line.to_string().len());
+        writeln!(
+            f,
+            "{} --> line {}, column {}",
+            gutter, self.line, self.column
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+        write!(
+            f,
+            "{} | {}^",
+            gutter,
+            " ".repeat(self.column.saturating_sub(1))
+        )
+    }
+}
+
+impl ParseError {
+    fn new(
+        input: &str,
+        error: peg::error::ParseError<peg::str::LineCol>,
+        starts: &[LineStart],
+    ) -> ParseError {
+        // Errors at the end of input are reported after the last token
+        let mut location = error.location;
+        if input[location.offset..].trim().is_empty() {
+            let offset = input.trim_end().len();
+            if offset < location.offset {
+                location = peg::Parse::position_repr(input, offset);
+            }
+        }
+
+        let lines = input.lines().collect::<Vec<_>>();
+        let source_line = lines
+            .get(location.line - 1)
+            .unwrap_or(&"")
+            .trim_end_matches('\r')
+            .to_string();
+
+        // Of the sections which could continue at the last line the grammar
+        // started before the error, use the one which matches the furthest
+        let start = starts
+            .iter()
+            .map(|&(p, _)| p)
+            .filter(|&p| p <= error.location.offset)
+            .max();
+        let mut candidates = Vec::new();
+        for &(p, section) in starts {
+            if Some(p) == start && !candidates.contains(&section) {
+                candidates.push(section);
+            }
+        }
+        let section = start.and_then(|start| {
+            let rest = &input[start..];
+            candidates
+                .into_iter()
+                .rev()
+                .max_by_key(|&section| {
+                    match input::section_line(rest, &RefCell::new(Vec::new()), section) {
+                        Ok(()) => rest.len(),
+                        Err(e) => e.location.offset,
+                    }
+                })
+                .flatten()
+        });
+
+        let rest = &input[location.offset..];
+        let token = rest
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .next()
+            .unwrap_or("");
+        let found = if !token.is_empty() {
+            format!("`{}`", token)
+        } else if rest.is_empty() {
+            "end of input".to_string()
+        } else if rest.starts_with('\n') || rest.starts_with("\r\n") {
+            "end of line".to_string()
+        } else {
+            format!("`{}`", rest.chars().next().unwrap())
+        };
+
+        let mut expected = error
+            .expected
+            .tokens()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>();
+        expected.sort();
+
+        ParseError {
+            section,
+            line: location.line,
+            column: location.column,
+            found,
+            expected,
+            source_line,
+        }
+    }
+}
+
+/// Parse the contents of an input file
+pub fn parse(input: &str) -> Result<Input, ParseError> {
+    let starts = RefCell::new(Vec::new());
+    let blocks =
+        input::input(input, &starts).map_err(|e| ParseError::new(input, e, &starts.borrow()))?;
+
+    // Combine sections which may be repeated or in any order
+    let mut parsed = Input {
+        tables: Vec::new(),
+        fds: Vec::new(),
+        inds: Vec::new(),
+        stats: Vec::new(),
+    };
+    for block in blocks {
+        match block {
+            Block::Tables(tables) => parsed.tables.extend(tables),
+            Block::FDs(fds) => parsed.fds.extend(fds),
+            Block::INDs(inds) => parsed.inds.extend(inds),
+            Block::Stats(stats) => parsed.stats.extend(stats),
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// An entry written on a single line of an input file, used to identify
+/// dependencies after they are merged or reordered
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Entry {
+    Table(String),
+    Functional(String, Vec<String>, FloatOrd<f32>),
+    Inclusion(IND),
+    Stats(String, Option<String>),
+}
+
+/// Order the fields of an `IND` by those on the left-hand side
+/// (leaving those with mismatched fields alone)
+fn sorted_ind(ind: IND) -> IND {
+    if ind.left_fields.len() != ind.right_fields.len() {
+        return ind;
+    }
+
+    let mut pairs = ind
+        .left_fields
+        .into_iter()
+        .zip(ind.right_fields)
+        .collect::<Vec<_>>();
+    pairs.sort();
+    let (left_fields, right_fields) = pairs.into_iter().unzip();
+
+    IND {
+        left_table: ind.left_table,
+        left_fields,
+        right_table: ind.right_table,
+        right_fields,
+    }
+}
+
+/// Convert an `IND` from the input with fields in sorted order
+fn input_ind(ind: &(String, Vec<String>, String, Vec<String>)) -> IND {
+    sorted_ind(IND {
+        left_table: ind.0.parse().unwrap(),
+        left_fields: ind.1.iter().map(|f| f.parse().unwrap()).collect(),
+        right_table: ind.2.parse().unwrap(),
+        right_fields: ind.3.iter().map(|f| f.parse().unwrap()).collect(),
+    })
+}
+
+/// Produce the left-hand side of an FD as it is identified when merging
+fn fd_lhs(lhs: &[String]) -> Vec<String> {
+    lhs.iter().cloned().sorted().dedup().collect()
+}
+
+/// Produce the entries defined in some input
+pub fn entries(input: &Input) -> Vec<Entry> {
+    input
+        .tables
+        .iter()
+        .map(|t| Entry::Table(t.name.to_string()))
+        .chain(
+            input
+                .fds
+                .iter()
+                .map(|fd| Entry::Functional(fd.0.clone(), fd_lhs(&fd.1), FloatOrd(fd.3))),
+        )
+        .chain(
+            input
+                .inds
+                .iter()
+                .map(|ind| Entry::Inclusion(input_ind(ind))),
+        )
+        .chain(
+            input
+                .stats
+                .iter()
+                .map(|s| Entry::Stats(s.0.clone(), s.1.clone())),
+        )
+        .collect()
+}
+
+/// Lines of an input file along with the entries written on each line
+pub type Lines = Vec<(Vec<Entry>, String)>;
+
+/// A table, left-hand side and confidence of FDs which are merged, with
+/// the most certain FDs first
+type FDKey = (String, Vec<String>, Reverse<FloatOrd<f32>>);
+
+/// Produce the lines of each section of an input file in canonical form
+pub fn canonical_lines(input: &Input) -> Vec<(Section, Lines)> {
+    let mut sections = Vec::new();
+
+    // Fields keep their order since it is used by the heuristics
+    let tables = input
+        .tables
+        .iter()
+        .sorted_by_key(|t| t.name.clone())
+        .map(|table| {
+            let fields = table
+                .fields
+                .values()
+                .map(|f| format!("{}{}", if f.key { "*" } else { "" }, f.name))
+                .join(", ");
+            (
+                vec![Entry::Table(table.name.to_string())],
+                format!("{}({})", table.name, fields),
+            )
+        })
+        .collect::<Vec<_>>();
+    sections.push((Section::Tables, tables));
+
+    // Merge FDs with the same left-hand side and confidence as they are
+    // merged in a table
+    let mut fds: IndexMap<FDKey, Vec<String>> = IndexMap::new();
+    for fd in &input.fds {
+        let lhs = fd_lhs(&fd.1);
+        fds.entry((fd.0.clone(), lhs.clone(), Reverse(FloatOrd(fd.3))))
+            .or_default()
+            .extend(fd.2.iter().filter(|f| !lhs.contains(f)).cloned());
+    }
+    let fds = fds
+        .into_iter()
+        .filter(|(_, rhs)| !rhs.is_empty())
+        .sorted_by(|a, b| a.0.cmp(&b.0))
+        .map(|((table, lhs, Reverse(FloatOrd(confidence))), rhs)| {
+            let mut line = format!(
+                "{} {} -> {}",
+                table,
+                lhs.join(", "),
+                rhs.iter().sorted().dedup().join(", ")
+            );
+            if confidence < 1.0 {
+                line.push_str(&format!(" @{}", confidence));
+            }
+            (
+                vec![Entry::Functional(table, lhs, FloatOrd(confidence))],
+                line,
+            )
+        })
+        .collect::<Vec<_>>();
+    sections.push((Section::FDs, fds));
+
+    // Remove duplicate INDs and those implied by another
+    let all_inds = input
+        .inds
+        .iter()
+        .map(input_ind)
+        .filter(|ind| ind.left_table != ind.right_table || ind.left_fields != ind.right_fields)
+        .collect::<HashSet<_>>();
+    let inds = all_inds
+        .iter()
+        .filter(|ind| {
+            !all_inds.iter().any(|other| {
+                other != *ind
+                    && other.left_table == ind.left_table
+                    && other.right_table == ind.right_table
+                    && ind.is_subset(other)
+            })
+        })
+        .collect::<HashSet<_>>();
+    let mut ind_lines = Vec::new();
+    for ind in inds.iter().sorted_by_key(|i| {
+        (
+            i.left_table.clone(),
+            i.right_table.clone(),
+            i.left_fields.clone(),
+            i.right_fields.clone(),
+        )
+    }) {
+        // Write symmetric INDs once from the side which sorts first
+        let reverse = sorted_ind(ind.reverse());
+        let symmetric = inds.contains(&reverse);
+        if symmetric && (&ind.right_table, &ind.right_fields) < (&ind.left_table, &ind.left_fields)
+        {
+            continue;
+        }
+
+        let right_fields = if ind.left_fields == ind.right_fields {
+            "...".to_string()
+        } else {
+            ind.right_fields.iter().join(", ")
+        };
+        let line = format!(
+            "{} {} {} {} {}",
+            ind.left_table,
+            ind.left_fields.iter().join(", "),
+            if symmetric { "==" } else { "<=" },
+            ind.right_table,
+            right_fields
+        );
+        let mut ind_entries = vec![Entry::Inclusion((*ind).clone())];
+        if symmetric {
+            ind_entries.push(Entry::Inclusion(reverse));
+        }
+        ind_lines.push((ind_entries, line));
+    }
+    sections.push((Section::INDs, ind_lines));
+
+    // Statistics on tables come before those on their fields and later
+    // statistics replace earlier ones as they do when building a schema
+    let stats = input
+        .stats
+        .iter()
+        .rev()
+        .sorted_by_key(|s| (s.0.clone(), s.1.clone()))
+        .dedup_by(|a, b| (&a.0, &a.1) == (&b.0, &b.1))
+        .map(|s| {
+            let line = match (&s.1, s.3) {
+                (None, _) => format!("{} {}", s.0, s.2),
+                (Some(field), Some(max_length)) => {
+                    format!("{} {} {} {}", s.0, field, s.2, max_length)
+                }
+                (Some(field), None) => format!("{} {} {}", s.0, field, s.2),
+            };
+            (vec![Entry::Stats(s.0.clone(), s.1.clone())], line)
+        })
+        .collect::<Vec<_>>();
+    sections.push((Section::Stats, stats));
+
+    sections
+}
+
+/// Write input in canonical form with each section under a header,
+/// merging FDs and INDs and using shortcuts where possible
+pub fn write(input: &Input) -> String {
+    let mut output = String::new();
+    for (section, lines) in canonical_lines(input) {
+        if lines.is_empty() && section != Section::Tables {
+            continue;
+        }
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&section_text(section, lines));
+    }
+
+    output
+}
+
+/// Write the lines of a section under its header
+fn section_text(section: Section, lines: Lines) -> String {
+    let mut output = format!("[{}]\n", section.header());
+    for (_, line) in lines {
+        output.push_str(&line);
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Write a single section of input in canonical form
+pub fn write_section(input: &Input, section: Section) -> String {
+    canonical_lines(input)
+        .into_iter()
+        .find(|&(s, _)| s == section)
+        .map(|(s, lines)| section_text(s, lines))
+        .unwrap_or_default()
+}
+
+/// Write a `Schema` in the input format so it can be parsed again
+pub fn write_schema(schema: &Schema) -> String {
+    write(&Input::from_schema(schema))
+}
 
 peg::parser! {
-  pub grammar input() for str {
-    rule space()
-      = quiet!{[' '| '\t' | '\r' | '\n']+}
+  pub grammar input(starts: &RefCell<Vec<LineStart>>) for str {
+    rule comment()
+      = "#" [^'\n']*
+
+    // Whitespace within a line
+    rule _()
+      = quiet!{[' ' | '\t' | '\r']*}
+
+    rule __()
+      = quiet!{[' ' | '\t' | '\r']+}
+
+    // The separator between a table name and its fields
+    rule fields_sep()
+      = __ / expected!("fields")
+
+    // Whitespace which may span lines and include comments
+    rule ws()
+      = quiet!{([' ' | '\t' | '\r' | '\n'] / comment())*}
+
+    // A line containing only whitespace and possibly a comment
+    rule blank()
+      = quiet!{_ comment()? "\n"}
+
+    // The end of a line with content, possibly followed by a comment
+    rule end()
+      = quiet!{_ comment()? ("\n" / ![_])}
+
+    // Record the section a line is being parsed in, or `None` for headers
+    rule start(section: Option<Section>)
+      = p:position!() { starts.borrow_mut().push((p, section)); }
+
+    // A line of content preceded by any number of blank lines
+    rule line<T>(section: Option<Section>, r: rule<T>) -> T
+      = blank()* _ start(section) x:r() end() { x }
 
     rule identifier() -> String
-      = id:$(['A'..='Z' | 'a'..='z' | '_']+['A'..='Z' | 'a'..='z' | '0'..='9' | '_']*) { id.to_string() }
+      = quiet!{id:$(['A'..='Z' | 'a'..='z' | '_']+['A'..='Z' | 'a'..='z' | '0'..='9' | '_']*) { id.to_string() }}
+      / expected!("identifier")
 
     rule identifiers() -> Vec<String>
-      = i:identifier() **<1,> (space()? "," space()?) { i }
+      = i:identifier() **<1,> (_ "," _) { i }
 
     rule integer() -> usize
-      = i:$(['0'..='9']+) { i.parse().unwrap() }
+      = quiet!{i:$(['0'..='9']+) { i.parse().unwrap() }}
+      / expected!("integer")
 
     rule table() -> String
       = table:identifier() { table }
@@ -30,16 +710,17 @@
             name: parsed_name,
             key: key.is_some(),
             cardinality: None,
-            max_length: None
+            max_length: None,
+            field_type: None
           }
         }
 
     rule field_defines() -> Vec<Field>
-      = field_define() ** (space()? "," space()?)
+      = field_define() ** (ws() "," ws())
 
     rule create() -> Table
-      = table:table() space()?
-        "(" space()? fields:field_defines() space()? ")" {
+      = table:table() _
+        "(" ws() fields:field_defines() ws() ")" {
           let mut field_map = IndexMap::new();
           for field in fields {
             field_map.insert(field.name.clone(), field);
@@ -56,17 +737,28 @@
           t
         }
 
-    rule func_dep() -> (String, Vec<String>, Vec<String>)
-      = table:identifier() space() lhs:identifiers() space() "->"
-        space() rhs:identifiers() { (table, lhs, rhs) }
+    // The fraction of rows an approximate dependency holds on
+    rule confidence() -> f32
+      = c:$(quiet!{['0'..='9']+ ("." ['0'..='9']+)?} / expected!("confidence")) {?
+          match c.parse::<f32>() {
+            Ok(c) if c > 0.0 && c <= 1.0 => Ok(c),
+            _ => Err("confidence between 0 and 1")
+          }
+        }
+
+    rule func_dep() -> (String, Vec<String>, Vec<String>, f32)
+      = table:identifier() fields_sep() lhs:identifiers() _ "->"
+        _ rhs:identifiers() confidence:(_ "@" _ c:confidence() { c })? {
+          (table, lhs, rhs, confidence.unwrap_or(1.0))
+        }
 
     rule inc_dir() -> String
       = dir:$("<=" / "==") { dir.to_string() }
 
     rule inc_dep() -> Vec<(String, Vec<String>, String, Vec<String>)>
-      = left_table:identifier() space() left_fields:identifiers()
-        space() dir:inc_dir() space()
-        right_table:identifier() space() maybe_right_fields:(ids:identifiers() { Some(ids) } / "..." { None })  {
+      = left_table:identifier() fields_sep() left_fields:identifiers()
+        _ dir:inc_dir() _
+        right_table:identifier() fields_sep() maybe_right_fields:(ids:identifiers() { Some(ids) } / "..." { None })  {
           let right_fields = match maybe_right_fields {
             Some(fields) => fields,
             None => left_fields.clone()
@@ -82,26 +774,247 @@
         }
 
     rule table_frequency() -> (String, Option<String>, usize, Option<usize>)
-      = table:identifier() space() count:integer() {
+      = table:identifier() __ count:integer() {
         (table, None, count, None)
       }
 
     rule column_frequency() -> (String, Option<String>, usize, Option<usize>)
-      = table:identifier() space() column:identifier() space() count:integer() space() max_length:integer() {
-        (table, Some(column), count, Some(max_length))
+      = table:identifier() __ column:identifier() __ count:integer()
+        max_length:(__ max_length:integer() { max_length })? {
+        (table, Some(column), count, max_length)
       }
 
     rule frequency() -> (String, Option<String>, usize, Option<usize>)
       = table_frequency() / column_frequency()
 
-    pub rule input() -> (Vec<Table>, Vec<(String, Vec<String>, Vec<String>)>,
-              Vec<(String, Vec<String>, String, Vec<String>)>,
-              Vec<(String, Option<String>, usize, Option<usize>)>)
-      = tables:(create() **<1,> "\n") "\n"*
-        func_deps:(func_dep() ** "\n") "\n"*
-        inc_deps:(inc_dep() ** "\n") "\n"*
-        frequencies:((frequency() ** "\n"))? "\n"* {
-          (tables, func_deps, inc_deps.into_iter().flat_map(|i| i).collect::<Vec<_>>(), frequencies.unwrap_or(Vec::new()))
+    rule header(name: &'static str)
+      = line(None, <"[" _ (quiet!{n:identifier() {? if n == name { Ok(()) } else { Err(name) } }} / expected!(name)) _ "]">)
+
+    // Sections identified only by their order
+    rule ordered_sections() -> Vec<Block>
+      = tables:line(Some(Section::Tables), <create()>)+
+        func_deps:line(Some(Section::FDs), <func_dep()>)*
+        inc_deps:line(Some(Section::INDs), <inc_dep()>)*
+        frequencies:line(Some(Section::Stats), <frequency()>)* {
+          vec![
+            Block::Tables(tables),
+            Block::FDs(func_deps),
+            Block::INDs(inc_deps.into_iter().flatten().collect()),
+            Block::Stats(frequencies),
+          ]
         }
+
+    // A section starting with an explicit header
+    rule headed_section() -> Block
+      = header("tables") tables:line(Some(Section::Tables), <create()>)* { Block::Tables(tables) }
+      / header("fds") func_deps:line(Some(Section::FDs), <func_dep()>)* { Block::FDs(func_deps) }
+      / header("inds") inc_deps:line(Some(Section::INDs), <inc_dep()>)* { Block::INDs(inc_deps.into_iter().flatten().collect()) }
+      / header("stats") frequencies:line(Some(Section::Stats), <frequency()>)* { Block::Stats(frequencies) }
+
+    pub rule input() -> Vec<Block>
+      = ordered:ordered_sections()? headed:headed_section()*
+        blank()* _ comment()? ![_] {
+          ordered.unwrap_or_default().into_iter().chain(headed).collect()
+        }
+
+    // A single line of a section, or a header of any name, used to find
+    // how far each section which could continue at a position matches
+    rule section_is(section: Option<Section>, expected: Option<Section>)
+      = {? if section == expected { Ok(()) } else { Err("section") } }
+
+    pub rule section_line(section: Option<Section>)
+      = section_is(section, Some(Section::Tables)) create() end()
+      / section_is(section, Some(Section::FDs)) func_dep() end()
+      / section_is(section, Some(Section::INDs)) inc_dep() end()
+      / section_is(section, Some(Section::Stats)) frequency() end()
+      / section_is(section, None) "[" _ identifier() _ "]" end()
   }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_valid() {
+        let parsed = parse("foo(*bar, baz)\n\nfoo bar -> baz\n").unwrap();
+        assert_eq!(parsed.tables.len(), 1);
+        assert_eq!(parsed.fds.len(), 1);
+    }
+
+    #[test]
+    fn parse_confidence() {
+        let parsed = parse("foo(*bar, baz, quux)\n\nfoo baz -> quux @0.98\n").unwrap();
+        assert_eq!(parsed.fds[0].3, 0.98);
+        assert!(write(&parsed).contains("foo baz -> quux @0.98\n"));
+        assert!(parse("foo(*bar, baz)\n\nfoo baz -> bar @1.5\n").is_err());
+    }
+
+    #[test]
+    fn parse_comments_and_blank_lines() {
+        let plain =
+            parse("foo(*bar, baz)\nquux(*corge)\n\nfoo bar -> baz\n\nfoo bar <= quux corge\n")
+                .unwrap();
+        let annotated = parse(
+            "# Tables\r\n  foo(*bar, # the key\r\n      baz)\r\n\r\n\
+             quux(*corge)  # trailing\r\n\r\n\
+             # Mined with confidence 0.9\r\n\tfoo bar -> baz\r\n\r\n\
+             foo bar <= quux corge # foreign key\r\n\r\n# End",
+        )
+        .unwrap();
+
+        assert_eq!(
+            annotated
+                .tables
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>(),
+            plain
+                .tables
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(annotated.fds, plain.fds);
+        assert_eq!(annotated.inds, plain.inds);
+    }
+
+    #[test]
+    fn parse_headers() {
+        let parsed = parse(
+            "[inds]\nfoo bar <= quux corge\n\n[fds]\n\n[tables]\nfoo(*bar, baz)\n\n\
+             [stats]\nfoo 3\n[tables]\nquux(*corge)\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            parsed
+                .tables
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>(),
+            vec!["foo(*bar, baz)", "quux(*corge)"]
+        );
+        assert!(parsed.fds.is_empty());
+        assert_eq!(parsed.inds.len(), 1);
+        assert_eq!(parsed.stats.len(), 1);
+    }
+
+    #[test]
+    fn parse_headers_after_ordered() {
+        let parsed = parse("foo(*bar, baz)\n\nfoo bar -> baz\n\n[stats]\nfoo 3\n").unwrap();
+        assert_eq!(parsed.fds.len(), 1);
+        assert_eq!(parsed.stats.len(), 1);
+    }
+
+    #[test]
+    fn parse_error_header_section() {
+        let err = parse("[inds]\nfoo bar <= quux corge\nfoo bar -> quux\n")
+            .err()
+            .unwrap();
+        assert_eq!(err.section, Some(Section::INDs));
+        assert_eq!(err.line, 3);
+    }
+
+    #[test]
+    fn parse_error_location() {
+        let err = parse("foo(*bar, baz)\n\nfoo bar => baz\n").err().unwrap();
+        assert_eq!(err.section, Some(Section::FDs));
+        assert_eq!((err.line, err.column), (3, 9));
+        assert_eq!(err.found, "`=>`");
+        assert!(err.expected.contains(&"\"->\"".to_string()));
+    }
+
+    #[test]
+    fn parse_error_section_stats() {
+        let err = parse("foo(*bar, baz)\n\nfoo bar -> baz\n\nfoo bar 3 baz\n")
+            .err()
+            .unwrap();
+        assert_eq!(err.section, Some(Section::Stats));
+        assert_eq!(err.line, 5);
+    }
+
+    #[test]
+    fn parse_error_missing_fields() {
+        let err = parse("foo(*bar)\nbaz(*quux)\n\nfoo bar <= baz\n")
+            .err()
+            .unwrap();
+        assert_eq!(err.section, Some(Section::INDs));
+        assert_eq!((err.line, err.column), (4, 15));
+        assert_eq!(err.expected, vec!["fields"]);
+    }
+
+    #[test]
+    fn parse_error_stray_header() {
+        let err = parse("foo(*bar, baz)\n\nfoo bar -> baz\n[oops]\n")
+            .err()
+            .unwrap();
+        assert_eq!(err.section, None);
+        assert_eq!((err.line, err.column), (4, 2));
+        assert_eq!(err.expected, vec!["fds", "inds", "stats", "tables"]);
+    }
+
+    #[test]
+    fn parse_error_expected_names() {
+        let err = parse("foo(*bar, 1baz)\n").err().unwrap();
+        assert_eq!(err.section, Some(Section::Tables));
+        assert_eq!(err.expected, vec!["\"*\"", "identifier"]);
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let input = "[tables]\nfoo(*bar, baz, quux)\ngrault(*bar, baz)\n\n\
+                     [fds]\nfoo bar -> baz\nfoo baz -> quux\ngrault bar -> baz\n\n\
+                     [inds]\nfoo bar == grault ...\nfoo baz <= grault ...\n\n\
+                     [stats]\nfoo 10\nfoo bar 10 3\nfoo baz 5\n";
+        let output = write_schema(&parse(input).unwrap().into_schema());
+        assert_eq!(output, input);
+
+        let merged = parse(
+            "foo(*bar, baz, quux)\ngrault(*corge)\n\nfoo bar -> baz\nfoo bar -> quux\n\n\
+             foo bar <= grault corge\ngrault corge <= foo bar\n",
+        )
+        .unwrap();
+        assert_eq!(
+            write_schema(&merged.into_schema()),
+            "[tables]\nfoo(*bar, baz, quux)\ngrault(*corge)\n\n\
+             [fds]\nfoo bar -> baz, quux\n\n\
+             [inds]\nfoo bar == grault corge\n"
+        );
+
+        // Later statistics replace earlier ones
+        let replaced = parse("foo(*bar)\n\nfoo 10\nfoo bar 10 3\nfoo 20\n").unwrap();
+        assert_eq!(
+            write_section(&replaced, Section::Stats),
+            "[stats]\nfoo 20\nfoo bar 10 3\n"
+        );
+    }
+
+    #[test]
+    fn write_approximate_round_trip() {
+        let input = "[tables]\nfoo(*bar, baz, quux, corge)\n\n\
+                     [fds]\nfoo bar -> baz, corge, quux\n\
+                     foo baz -> corge @0.99\nfoo baz -> quux @0.9\n";
+
+        // FDs with the same LHS keep their own confidence
+        let output = write_schema(&parse(input).unwrap().into_schema());
+        assert_eq!(output, input);
+        assert_eq!(write_schema(&parse(&output).unwrap().into_schema()), input);
+    }
+
+    #[test]
+    fn write_canonical_cover() {
+        let parsed = parse(
+            "foo(*bar, baz, quux, corge)\n\nfoo bar -> baz\nfoo baz -> quux\n\
+             foo baz -> corge @0.9\n",
+        )
+        .unwrap();
+
+        // Implied FDs are not written and exact FDs stay apart from
+        // approximate FDs with the same LHS
+        assert_eq!(
+            write_section(&Input::from_schema(&parsed.into_schema()), Section::FDs),
+            "[fds]\nfoo bar -> baz, corge\nfoo baz -> quux\nfoo baz -> corge @0.9\n"
+        );
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::HashSet;
+
+use indexmap::IndexMap;
+use itertools::Itertools;
+use serde::de::Error;
+
+use crate::input::Input;
+use crate::model::{Field, FieldType, Schema, Table};
+use crate::symbols::FieldName;
+
+/// The version of the JSON document format which is read and written
+pub const VERSION: u32 = 1;
+
+/// A complete schema with its dependencies
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Document {
+    /// The version of the document format
+    pub version: u32,
+
+    /// All tables in the schema
+    pub tables: Vec<TableDocument>,
+
+    /// Inclusion dependencies between tables
+    #[serde(default)]
+    pub inds: Vec<INDDocument>,
+}
+
+/// A table along with its functional dependencies
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableDocument {
+    /// The name of the table
+    pub name: String,
+
+    /// Fields of the table in order
+    pub fields: Vec<FieldDocument>,
+
+    /// The number of rows in the table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row_count: Option<usize>,
+
+    /// Fields forming the partition key in a wide-column store
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub partition_key: Vec<String>,
+
+    /// Fields forming the clustering key in a wide-column store
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clustering_key: Vec<String>,
+
+    /// Functional dependencies which hold on the table
+    #[serde(default)]
+    pub fds: Vec<FDDocument>,
+}
+
+/// A single field of a table
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FieldDocument {
+    /// The name of the field
+    pub name: String,
+
+    /// Whether the field is part of the primary key
+    #[serde(default)]
+    pub key: bool,
+
+    /// The type of values stored in the field
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub field_type: Option<FieldType>,
+
+    /// The number of distinct values of the field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cardinality: Option<usize>,
+
+    /// The maximum length of values of the field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+}
+
+/// A functional dependency within a table
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FDDocument {
+    pub lhs: Vec<String>,
+    pub rhs: Vec<String>,
+
+    /// The fraction of rows an approximate dependency holds on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+}
+
+/// An inclusion dependency between two tables
+#[derive(Debug, Serialize, Deserialize)]
+pub struct INDDocument {
+    pub left_table: String,
+    pub left_fields: Vec<String>,
+    pub right_table: String,
+    pub right_fields: Vec<String>,
+}
+
+/// Read a schema from a JSON document
+pub fn parse(input: &str) -> Result<Input, serde_json::Error> {
+    let document: Document = serde_json::from_str(input)?;
+    if document.version != VERSION {
+        return Err(serde_json::Error::custom(format!(
+            "unsupported document version {} (expected {})",
+            document.version, VERSION
+        )));
+    }
+
+    let mut parsed = Input {
+        tables: Vec::new(),
+        fds: Vec::new(),
+        inds: Vec::new(),
+        stats: Vec::new(),
+    };
+
+    for table_document in document.tables {
+        let mut table = Table {
+            name: table_document.name.parse().unwrap(),
+            fields: IndexMap::new(),
+            partition_key: table_document
+                .partition_key
+                .iter()
+                .map(|f| f.parse().unwrap())
+                .collect(),
+            clustering_key: table_document
+                .clustering_key
+                .iter()
+                .map(|f| f.parse().unwrap())
+                .collect(),
+            ..Default::default()
+        };
+
+        // Statistics are added later so they are validated with the rest
+        if let Some(row_count) = table_document.row_count {
+            parsed
+                .stats
+                .push((table_document.name.clone(), None, row_count, None));
+        }
+
+        for field in table_document.fields {
+            // Field statistics are given together as in the input format
+            match (field.cardinality, field.max_length) {
+                (Some(cardinality), max_length) => parsed.stats.push((
+                    table_document.name.clone(),
+                    Some(field.name.clone()),
+                    cardinality,
+                    max_length,
+                )),
+                (None, Some(_)) => {
+                    return Err(serde_json::Error::custom(format!(
+                        "field {} of {} has a max_length without a cardinality",
+                        field.name, table_document.name
+                    )))
+                }
+                (None, None) => {}
+            }
+            table.fields.insert(
+                field.name.parse().unwrap(),
+                Field {
+                    name: field.name.parse().unwrap(),
+                    key: field.key,
+                    cardinality: None,
+                    max_length: None,
+                    field_type: field.field_type,
+                },
+            );
+        }
+        table.add_pk_fd();
+
+        for fd in table_document.fds {
+            parsed.fds.push((
+                table_document.name.clone(),
+                fd.lhs,
+                fd.rhs,
+                fd.confidence.unwrap_or(1.0),
+            ));
+        }
+
+        parsed.tables.push(table);
+    }
+
+    for ind in document.inds {
+        parsed.inds.push((
+            ind.left_table,
+            ind.left_fields,
+            ind.right_table,
+            ind.right_fields,
+        ));
+    }
+
+    Ok(parsed)
+}
+
+/// Produce the names of a set of fields in sorted order
+fn sorted_names(fields: &HashSet<FieldName>) -> Vec<String> {
+    fields.iter().map(|f| f.to_string()).sorted().collect()
+}
+
+/// Convert a schema into a JSON document with tables, FDs and INDs sorted
+pub fn document(schema: &Schema) -> Document {
+    let tables = schema
+        .tables
+        .values()
+        .sorted_by_key(|t| t.name.clone())
+        .map(|table| TableDocument {
+            name: table.name.to_string(),
+            fields: table
+                .fields
+                .values()
+                .map(|f| FieldDocument {
+                    name: f.name.to_string(),
+                    key: f.key,
+                    field_type: f.field_type.clone(),
+                    cardinality: f.cardinality,
+                    max_length: f.max_length,
+                })
+                .collect(),
+            row_count: table.row_count,
+            partition_key: table.partition_key.iter().map(|f| f.to_string()).collect(),
+            clustering_key: table.clustering_key.iter().map(|f| f.to_string()).collect(),
+            fds: table
+                .all_fds()
+                .map(|fd| FDDocument {
+                    lhs: sorted_names(&fd.lhs),
+                    rhs: sorted_names(&fd.rhs),
+                    confidence: if fd.is_exact() {
+                        None
+                    } else {
+                        Some(fd.confidence)
+                    },
+                })
+                .sorted_by(|a, b| (&a.lhs, &a.rhs).cmp(&(&b.lhs, &b.rhs)))
+                .collect(),
+        })
+        .collect();
+
+    let inds = schema
+        .inds
+        .values()
+        .flatten()
+        .map(|ind| INDDocument {
+            left_table: ind.left_table.to_string(),
+            left_fields: ind.left_fields.iter().map(|f| f.to_string()).collect(),
+            right_table: ind.right_table.to_string(),
+            right_fields: ind.right_fields.iter().map(|f| f.to_string()).collect(),
+        })
+        .sorted_by(|a, b| {
+            (
+                &a.left_table,
+                &a.right_table,
+                &a.left_fields,
+                &a.right_fields,
+            )
+                .cmp(&(
+                    &b.left_table,
+                    &b.right_table,
+                    &b.left_fields,
+                    &b.right_fields,
+                ))
+        })
+        .collect();
+
+    Document {
+        version: VERSION,
+        tables,
+        inds,
+    }
+}
+
+/// Write a schema as a JSON document
+pub fn write_schema(schema: &Schema) -> String {
+    serde_json::to_string_pretty(&document(schema)).unwrap() + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+    use crate::symbols::TableName;
+
+    #[test]
+    fn round_trip() {
+        let mut parsed = input::parse(
+            "users(*id, name, email)\nposts(*id, user_id)\n\n\
+             users email -> name @0.9\n\nposts user_id <= users id\n\n\
+             users 10\nusers email 10 25\n",
+        )
+        .unwrap();
+        parsed.tables[0].fields[2].field_type = Some(FieldType::Varchar(Some(25)));
+        let output = write_schema(&parsed.into_schema());
+
+        let schema = parse(&output).unwrap().into_schema();
+        assert_eq!(write_schema(&schema), output);
+
+        let users = &schema.tables[&TableName::from("users")];
+        assert_eq!(users.row_count, Some(10));
+        assert_eq!(users.fields[2].cardinality, Some(10));
+        assert_eq!(users.fields[2].max_length, Some(25));
+        assert_eq!(
+            users.fields[2].field_type,
+            Some(FieldType::Varchar(Some(25)))
+        );
+        assert!(output.contains("\"type\": {\n            \"varchar\": 25\n"));
+        assert!(output.contains("\"confidence\": 0.9\n"));
+        assert_eq!(schema.inds.values().flatten().count(), 1);
+    }
+
+    #[test]
+    fn parse_field_stats() {
+        let parsed = parse(
+            "{\"version\": 1, \"tables\": [{\"name\": \"users\", \"fields\": [\
+             {\"name\": \"id\", \"key\": true, \"cardinality\": 10}]}]}",
+        )
+        .unwrap();
+        assert_eq!(
+            parsed.stats,
+            vec![("users".to_string(), Some("id".to_string()), 10, None)]
+        );
+
+        let error = parse(
+            "{\"version\": 1, \"tables\": [{\"name\": \"users\", \"fields\": [\
+             {\"name\": \"id\", \"key\": true, \"max_length\": 5}]}]}",
+        )
+        .err()
+        .unwrap();
+        assert!(error
+            .to_string()
+            .contains("field id of users has a max_length without a cardinality"));
+    }
+
+    #[test]
+    fn parse_version() {
+        let error = parse("{\"version\": 2, \"tables\": []}").err().unwrap();
+        assert!(error.to_string().contains("unsupported document version 2"));
+    }
+}
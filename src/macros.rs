@@ -67,7 +67,8 @@ macro_rules! field(
       name: FieldName::from($name),
       key: false,
       cardinality: Some(1),
-      max_length: Some(1)
+      max_length: Some(1),
+      field_type: None
     }
   };
   ($name:expr, $key:expr) => {
@@ -75,7 +76,8 @@ macro_rules! field(
       name: FieldName::from($name),
       key: $key,
       cardinality: Some(1),
-      max_length: Some(1)
+      max_length: Some(1),
+      field_type: None
     }
   };
   ($name:expr, $key:expr, $cardinality:expr, $max_length:expr) => {
@@ -83,7 +85,8 @@ macro_rules! field(
       name: FieldName::from($name),
       key: $key,
       cardinality: Some($cardinality),
-      max_length: Some($max_length)
+      max_length: Some($max_length),
+      field_type: None
     }
   };
 );
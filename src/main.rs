@@ -2,6 +2,7 @@
 #[cfg(test)]
 #[macro_use]
 extern crate collect_mac;
+extern crate csv;
 extern crate defaultmap;
 extern crate float_ord;
 extern crate itertools;
@@ -9,30 +10,51 @@
 extern crate log;
 extern crate indexmap;
 extern crate permutation;
+extern crate rusqlite;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
 extern crate string_intern;
 
+use std::collections::HashSet;
+use std::env;
+use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
+use std::path::Path;
 use std::str::FromStr;
 
-use argparse::{ArgumentParser, Store, StoreFalse, StoreOption, StoreTrue};
+use argparse::{ArgumentParser, Collect, List, Store, StoreFalse, StoreOption, StoreTrue};
+use itertools::Itertools;
 use log::LevelFilter;
 
 #[macro_use]
 mod macros;
+mod chase;
+mod check;
+mod cql;
+mod data;
 mod dependencies;
+mod discover;
+mod documents;
+mod format;
+mod json;
 mod model;
 mod normalize;
 mod simple_logger;
+mod sql;
+mod sqlite;
 mod symbols;
+mod validate;
 
 mod input;
 
-use crate::dependencies::{FDClosure, INDClosure};
-use crate::model::Schema;
-use crate::normalize::Normalizer;
+use crate::dependencies::{INDClosure, FD};
+use crate::normalize::{NormalForm, Normalizer};
 use crate::simple_logger::SimpleLogger;
+use crate::symbols::{FieldName, TableName};
 
 static LOGGER: SimpleLogger = SimpleLogger;
 
@@ -44,30 +66,669 @@ fn read_file(name: &str) -> Result<String, io::Error> {
     Ok(input_string)
 }
 
+/// The formats which can be used for input files
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum InputFormat {
+    Text,
+    Sql,
+    Cql,
+    Json,
+    JsonLines,
+    Sqlite,
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<InputFormat, String> {
+        match s {
+            "text" => Ok(InputFormat::Text),
+            "sql" => Ok(InputFormat::Sql),
+            "cql" => Ok(InputFormat::Cql),
+            "json" => Ok(InputFormat::Json),
+            "jsonl" => Ok(InputFormat::JsonLines),
+            "sqlite" => Ok(InputFormat::Sqlite),
+            _ => Err(format!("unknown input format {}", s)),
+        }
+    }
+}
+
+impl InputFormat {
+    /// Guess the format of an input file from its extension
+    fn from_path(path: &str) -> InputFormat {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("sql") | Some("ddl") => InputFormat::Sql,
+            Some("cql") => InputFormat::Cql,
+            Some("json") => InputFormat::Json,
+            Some("jsonl") | Some("ndjson") => InputFormat::JsonLines,
+            Some("sqlite") | Some("sqlite3") | Some("db") => InputFormat::Sqlite,
+            _ => InputFormat::Text,
+        }
+    }
+}
+
+/// Parse an input file in the given format, or one guessed from its extension
+fn parse_input(path: &str, input_format: Option<InputFormat>) -> Result<input::Input, String> {
+    let input_format = input_format.unwrap_or_else(|| InputFormat::from_path(path));
+
+    // Databases are binary so they are opened directly
+    if input_format == InputFormat::Sqlite {
+        return sqlite::read(path).map_err(|e| e.to_string());
+    }
+
+    let input_string = read_file(path).map_err(|e| e.to_string())?;
+    match input_format {
+        InputFormat::Text => input::parse(&input_string).map_err(|e| e.to_string()),
+        InputFormat::Sql => sql::parse(&input_string).map_err(|e| e.to_string()),
+        InputFormat::Cql => cql::parse(&input_string).map_err(|e| e.to_string()),
+        InputFormat::Json => json::parse(&input_string).map_err(|e| e.to_string()),
+        InputFormat::JsonLines => {
+            documents::parse(&data::table_name(path), &input_string).map_err(|e| e.to_string())
+        }
+        InputFormat::Sqlite => unreachable!(),
+    }
+}
+
+/// Load an input file, reporting any problems with the input and
+/// exiting if they prevent a schema from being built
+fn load_input(
+    path: &str,
+    input_format: Option<InputFormat>,
+    policy: &validate::Policy,
+) -> input::Input {
+    let parsed = match parse_input(path, input_format) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("error: {}: {}", path, e);
+            ::std::process::exit(1);
+        }
+    };
+
+    // Check the input for problems before building the schema
+    let problems = validate::validate(&parsed, policy);
+    for problem in &problems {
+        eprintln!("{}: {}", path, problem);
+    }
+    if problems
+        .iter()
+        .any(|p| p.severity == validate::Severity::Error)
+    {
+        ::std::process::exit(1);
+    }
+
+    parsed
+}
+
+/// Load a schema from an input file, exiting if it has problems
+fn load_schema(
+    path: &str,
+    input_format: Option<InputFormat>,
+    policy: &validate::Policy,
+) -> model::Schema {
+    load_input(path, input_format, policy).into_schema()
+}
+
+/// The formats which can be used to write the normalized schema
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OutputFormat {
+    Text,
+    Eson,
+    Sql,
+    Cql,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "eson" => Ok(OutputFormat::Eson),
+            "sql" => Ok(OutputFormat::Sql),
+            "cql" => Ok(OutputFormat::Cql),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown output format {}", s)),
+        }
+    }
+}
+
 struct Options {
     input: String,
+    input_format: Option<InputFormat>,
+    format: OutputFormat,
+    dialect: sql::Dialect,
     normalize: bool,
+    target: NormalForm,
     subsume: bool,
     ignore_missing: bool,
     minimize: bool,
     retain_fks: bool,
     use_stats: bool,
     fd_threshold: Option<f32>,
+    min_confidence: Option<f32>,
+    keys: Vec<String>,
     show_dependencies: bool,
+    check_lossless: bool,
+    report_lost_fds: bool,
     log_level: String,
 }
 
+/// Rewrite input files in canonical form
+fn fmt_command(args: Vec<String>) {
+    let mut files: Vec<String> = Vec::new();
+    let mut check = false;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Format input files in canonical form");
+        ap.refer(&mut check).add_option(
+            &["--check"],
+            StoreTrue,
+            "Report files which are not formatted instead of rewriting them",
+        );
+        ap.refer(&mut files)
+            .required()
+            .add_argument("files", List, "Input files to format");
+        if let Err(code) = ap.parse(args, &mut io::stdout(), &mut io::stderr()) {
+            ::std::process::exit(code);
+        }
+    }
+
+    let mut unformatted = false;
+    for file in &files {
+        let formatted = read_file(file)
+            .map_err(|e| e.to_string())
+            .and_then(|source| {
+                format::format(&source)
+                    .map(|formatted| (source, formatted))
+                    .map_err(|e| e.to_string())
+            });
+        let (source, formatted) = match formatted {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("error: {}: {}", file, e);
+                ::std::process::exit(1);
+            }
+        };
+
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{} is not formatted", file);
+            unformatted = true;
+        } else {
+            fs::write(file, formatted).unwrap();
+        }
+    }
+
+    if unformatted {
+        ::std::process::exit(1);
+    }
+}
+
+/// Compute statistics from data files and write them as input,
+/// optionally merging them into an existing input file
+fn stats_command(args: Vec<String>) {
+    let mut files: Vec<String> = Vec::new();
+    let mut merge: Option<String> = None;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description(
+            "Compute statistics from CSV or JSON lines files with one file per table",
+        );
+        ap.refer(&mut merge).add_option(
+            &["-m", "--merge"],
+            StoreOption,
+            "An input file to update with the statistics",
+        );
+        ap.refer(&mut files).required().add_argument(
+            "files",
+            List,
+            "CSV or JSON lines files named after each table",
+        );
+        if let Err(code) = ap.parse(args, &mut io::stdout(), &mut io::stderr()) {
+            ::std::process::exit(code);
+        }
+    }
+
+    let mut stats = Vec::new();
+    for file in &files {
+        match data::read_relation(file) {
+            Ok(relation) => stats.extend(relation.stats()),
+            Err(e) => {
+                eprintln!("error: {}: {}", file, e);
+                ::std::process::exit(1);
+            }
+        }
+    }
+    let mut computed = input::Input {
+        tables: Vec::new(),
+        fds: Vec::new(),
+        inds: Vec::new(),
+        stats,
+    };
+
+    let merge = match merge {
+        Some(merge) => merge,
+        None => {
+            print!("{}", input::write_section(&computed, input::Section::Stats));
+            return;
+        }
+    };
+
+    // Only keep statistics for tables and fields in the input file
+    let merged = read_file(&merge)
+        .map_err(|e| e.to_string())
+        .and_then(|source| {
+            let parsed = input::parse(&source).map_err(|e| e.to_string())?;
+            computed.stats.retain(|stat| {
+                parsed.tables.iter().any(|t| {
+                    t.name.as_ref() == stat.0
+                        && stat
+                            .1
+                            .as_ref()
+                            .is_none_or(|f| t.fields.contains_key(f.as_str()))
+                })
+            });
+            format::merge_stats(&source, &computed).map_err(|e| e.to_string())
+        });
+    match merged {
+        Ok(merged) => fs::write(&merge, merged).unwrap(),
+        Err(e) => {
+            eprintln!("error: {}: {}", merge, e);
+            ::std::process::exit(1);
+        }
+    }
+}
+
+/// Test the dependencies of a schema against data files
+fn check_data_command(args: Vec<String>) {
+    let mut schema_file = String::new();
+    let mut input_format: Option<InputFormat> = None;
+    let mut files: Vec<String> = Vec::new();
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Check FDs and INDs against CSV or JSON lines files");
+        ap.refer(&mut input_format).add_option(
+            &["-f", "--input-format"],
+            StoreOption,
+            "The format of the input file (text, sql, cql, json, jsonl, or sqlite)",
+        );
+        ap.refer(&mut schema_file)
+            .required()
+            .add_argument("input", Store, "The schema to check");
+        ap.refer(&mut files).required().add_argument(
+            "files",
+            List,
+            "CSV or JSON lines files named after each table",
+        );
+        if let Err(code) = ap.parse(args, &mut io::stdout(), &mut io::stderr()) {
+            ::std::process::exit(code);
+        }
+    }
+
+    let parsed = load_input(&schema_file, input_format, &validate::Policy::default());
+    let mut relations = Vec::new();
+    for file in &files {
+        match data::read_relation(file) {
+            Ok(relation) => relations.push(relation),
+            Err(e) => {
+                eprintln!("error: {}: {}", file, e);
+                ::std::process::exit(1);
+            }
+        }
+    }
+
+    let mut failed = false;
+    for check in check::check_input(&parsed, &relations) {
+        match check {
+            Ok(check) => {
+                failed = failed || !check.holds();
+                println!("{}", check);
+            }
+            Err(e) => {
+                failed = true;
+                eprintln!("error: {}", e);
+            }
+        }
+    }
+
+    if failed {
+        ::std::process::exit(1);
+    }
+}
+
+/// Answer a question about the dependencies of a table in a schema
+fn query_command(args: Vec<String>) {
+    let mut query = String::new();
+    let mut table_name = String::new();
+    let mut arguments: Vec<String> = Vec::new();
+    let mut input_format: Option<InputFormat> = None;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description(
+            "Find the fields determined by a set of fields in a table \
+             (closure FIELDS INPUT) or check if an FD holds (implies LHS RHS INPUT)",
+        );
+        ap.refer(&mut input_format).add_option(
+            &["-f", "--input-format"],
+            StoreOption,
+            "The format of the input file (text, sql, cql, json, jsonl, or sqlite)",
+        );
+        ap.refer(&mut query).required().add_argument(
+            "query",
+            Store,
+            "The question to ask (closure or implies)",
+        );
+        ap.refer(&mut table_name)
+            .required()
+            .add_argument("table", Store, "The table to query");
+        ap.refer(&mut arguments).required().add_argument(
+            "arguments",
+            List,
+            "Comma-separated lists of fields in the table followed by the schema to query",
+        );
+        if let Err(code) = ap.parse(args, &mut io::stdout(), &mut io::stderr()) {
+            ::std::process::exit(code);
+        }
+    }
+
+    let expected = match query.as_str() {
+        "closure" => 2,
+        "implies" => 3,
+        _ => {
+            eprintln!("error: unknown query {}", query);
+            ::std::process::exit(2);
+        }
+    };
+    if arguments.len() != expected {
+        eprintln!(
+            "error: {} expects {} arguments, got {}",
+            query,
+            expected,
+            arguments.len()
+        );
+        ::std::process::exit(2);
+    }
+    let schema_file = arguments.pop().unwrap();
+
+    let parsed = load_input(&schema_file, input_format, &validate::Policy::default());
+
+    // The FDs of a table already include everything they imply so
+    // proofs are clearer using the FDs as they were declared
+    let mut declared = Vec::new();
+    for table in parsed
+        .tables
+        .iter()
+        .filter(|t| t.name.as_ref() == table_name)
+    {
+        let (lhs, rhs): (Vec<_>, Vec<_>) = table.fields.values().partition(|f| f.key);
+        if lhs.is_empty() || rhs.is_empty() {
+            continue;
+        }
+        declared.push(FD {
+            lhs: lhs.iter().map(|f| f.name.clone()).collect(),
+            rhs: rhs.iter().map(|f| f.name.clone()).collect(),
+            confidence: 1.0,
+        });
+    }
+    for fd in parsed.fds.iter().filter(|fd| fd.0 == table_name) {
+        declared.push(FD {
+            lhs: fd.1.iter().map(|f| f.parse().unwrap()).collect(),
+            rhs: fd.2.iter().map(|f| f.parse().unwrap()).collect(),
+            confidence: fd.3,
+        });
+    }
+
+    let schema = parsed.into_schema();
+    let table = match schema.tables.get(&table_name.parse::<TableName>().unwrap()) {
+        Some(table) => table,
+        None => {
+            eprintln!("error: {}: no table {}", schema_file, table_name);
+            ::std::process::exit(1);
+        }
+    };
+    let parse_fields = |field_list: &str| {
+        let mut fields = HashSet::new();
+        for name in field_list
+            .split(',')
+            .map(|f| f.trim())
+            .filter(|f| !f.is_empty())
+        {
+            let field = name.parse::<FieldName>().unwrap();
+            if !table.fields.contains_key(&field) {
+                eprintln!(
+                    "error: {}: no field {} in {}",
+                    schema_file, name, table_name
+                );
+                ::std::process::exit(1);
+            }
+            fields.insert(field);
+        }
+        fields
+    };
+    let fields = parse_fields(&arguments[0]);
+
+    let lhs = fields.iter().sorted().join(", ");
+    if query == "implies" {
+        let fd = FD {
+            lhs: fields,
+            rhs: parse_fields(&arguments[1]),
+            confidence: 1.0,
+        };
+        let rhs = fd.rhs.iter().sorted().join(", ");
+        if table.implies(&fd) {
+            println!("{} {} -> {} is implied", table.name, lhs, rhs);
+            return;
+        }
+
+        let determined = table.determined_fields(&fd.lhs);
+        println!(
+            "{} {} -> {} is not implied ({} not determined exactly)",
+            table.name,
+            lhs,
+            rhs,
+            fd.rhs
+                .iter()
+                .filter(|f| determined.get(*f).is_none_or(|&c| c < 1.0))
+                .sorted()
+                .join(", ")
+        );
+        ::std::process::exit(1);
+    }
+
+    let determined = table.determined_fields(&fields);
+    if determined.is_empty() {
+        println!("{} {} determines no other fields", table.name, lhs);
+        return;
+    }
+
+    // Fields inferred from approximate FDs are only as certain as the weakest
+    let confidence = determined.values().cloned().fold(1.0, f32::min);
+    print!(
+        "{} {} -> {}",
+        table.name,
+        lhs,
+        determined.keys().sorted().join(", ")
+    );
+    if confidence < 1.0 {
+        print!(" @{}", confidence);
+    }
+    println!();
+
+    let mut proof = dependencies::closure_proof(&declared, &fields);
+    let known = fields
+        .iter()
+        .chain(proof.iter().flat_map(|(_, added)| added))
+        .cloned()
+        .collect();
+    proof.extend(table.closure_proof(&known));
+    for (fd, added) in proof {
+        print!(
+            "  by {} -> {}",
+            fd.lhs.iter().sorted().join(", "),
+            added.iter().join(", ")
+        );
+        if !fd.is_exact() {
+            print!(" @{}", fd.confidence);
+        }
+        println!();
+    }
+}
+
+/// List the candidate keys of tables in a schema
+fn keys_command(args: Vec<String>) {
+    let mut schema_file = String::new();
+    let mut input_format: Option<InputFormat> = None;
+    let mut use_stats = false;
+    let mut table_names: Vec<String> = Vec::new();
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("List the candidate keys of each table with their scores");
+        ap.refer(&mut input_format).add_option(
+            &["-f", "--input-format"],
+            StoreOption,
+            "The format of the input file (text, sql, cql, json, jsonl, or sqlite)",
+        );
+        ap.refer(&mut use_stats).add_option(
+            &["-s", "--use-stats"],
+            StoreTrue,
+            "Choose the key with the best score as the primary key",
+        );
+        ap.refer(&mut schema_file)
+            .required()
+            .add_argument("input", Store, "The schema to examine");
+        ap.refer(&mut table_names).add_argument(
+            "tables",
+            List,
+            "The tables to examine (all tables by default)",
+        );
+        if let Err(code) = ap.parse(args, &mut io::stdout(), &mut io::stderr()) {
+            ::std::process::exit(code);
+        }
+    }
+
+    let policy = validate::Policy {
+        use_stats,
+        ..Default::default()
+    };
+    let mut schema = load_schema(&schema_file, input_format, &policy);
+    for name in &table_names {
+        if !schema
+            .tables
+            .contains_key(&name.parse::<TableName>().unwrap())
+        {
+            eprintln!("error: {}: no table {}", schema_file, name);
+            ::std::process::exit(1);
+        }
+    }
+
+    let mut tables = schema
+        .tables
+        .values_mut()
+        .filter(|t| table_names.is_empty() || table_names.iter().any(|n| t.name.as_ref() == n))
+        .collect::<Vec<_>>();
+    tables.sort_by_key(|t| t.name.clone());
+    for (i, table) in tables.into_iter().enumerate() {
+        if use_stats {
+            table.set_primary_key(true);
+        }
+        if i > 0 {
+            println!();
+        }
+
+        // The table is shown with its primary key marked
+        println!("{}", table);
+        for key in table.candidate_keys() {
+            print!("  {}", key.iter().join(", "));
+            if let Some(score) = table.key_score(&key.into_iter().collect()) {
+                print!(" (score {:.4})", score);
+            }
+            println!();
+        }
+    }
+}
+
+/// Discover FDs from CSV files and write them as input
+fn discover_command(args: Vec<String>) {
+    let mut files: Vec<String> = Vec::new();
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description(
+            "Discover FDs and INDs from CSV or JSON lines files with one file per table",
+        );
+        ap.refer(&mut files).required().add_argument(
+            "files",
+            List,
+            "CSV or JSON lines files named after each table",
+        );
+        if let Err(code) = ap.parse(args, &mut io::stdout(), &mut io::stderr()) {
+            ::std::process::exit(code);
+        }
+    }
+
+    let mut relations = Vec::new();
+    for file in &files {
+        let relation = match data::read_relation(file) {
+            Ok(relation) => relation,
+            Err(e) => {
+                eprintln!("error: {}: {}", file, e);
+                ::std::process::exit(1);
+            }
+        };
+        if relation.columns.len() > discover::MAX_COLUMNS {
+            eprintln!(
+                "error: {}: FDs can only be discovered on up to {} columns",
+                file,
+                discover::MAX_COLUMNS
+            );
+            ::std::process::exit(1);
+        }
+        relations.push(relation);
+    }
+
+    let discovered = discover::discover_input(&relations);
+    print!("{}", input::write(&discovered));
+}
+
 fn main() {
+    // Subcommands are given before any other arguments
+    let args = env::args().collect::<Vec<_>>();
+    if args.len() > 1 {
+        let subcommand_args = Some(format!("{} {}", args[0], args[1]))
+            .into_iter()
+            .chain(args[2..].iter().cloned())
+            .collect::<Vec<_>>();
+        match args[1].as_str() {
+            "fmt" => return fmt_command(subcommand_args),
+            "discover" => return discover_command(subcommand_args),
+            "stats" => return stats_command(subcommand_args),
+            "check-data" => return check_data_command(subcommand_args),
+            "query" => return query_command(subcommand_args),
+            "keys" => return keys_command(subcommand_args),
+            _ => {}
+        }
+    }
+
     let mut options = Options {
         input: "".to_string(),
+        input_format: None,
+        format: OutputFormat::Text,
+        dialect: sql::Dialect::PostgreSQL,
         normalize: true,
+        target: NormalForm::Bcnf,
         subsume: true,
         ignore_missing: false,
         minimize: false,
         retain_fks: false,
         use_stats: false,
         fd_threshold: None,
+        min_confidence: None,
+        keys: Vec::new(),
         show_dependencies: false,
+        check_lossless: false,
+        report_lost_fds: false,
         log_level: "Off".to_string(),
     };
     {
@@ -76,8 +737,28 @@ fn main() {
         ap.refer(&mut options.input)
             .add_argument("input", Store, "Example to run")
             .required();
+        ap.refer(&mut options.input_format).add_option(
+            &["-f", "--input-format"],
+            StoreOption,
+            "The format of the input file (text, sql, cql, json, jsonl, or sqlite)",
+        );
+        ap.refer(&mut options.format).add_option(
+            &["-o", "--format"],
+            Store,
+            "The format of the output (text, eson, sql, cql, or json)",
+        );
+        ap.refer(&mut options.dialect).add_option(
+            &["--dialect"],
+            Store,
+            "The SQL dialect to write (postgresql, mysql, or sqlite)",
+        );
         ap.refer(&mut options.normalize)
             .add_option(&["--no-norm"], StoreFalse, "Don't normalize");
+        ap.refer(&mut options.target).add_option(
+            &["--target"],
+            Store,
+            "The normal form to normalize to (bcnf or 3nf)",
+        );
         ap.refer(&mut options.subsume).add_option(
             &["--no-subsume"],
             StoreFalse,
@@ -86,7 +767,7 @@ fn main() {
         ap.refer(&mut options.ignore_missing).add_option(
             &["-i", "--ignore-missing"],
             StoreTrue,
-            "Ignore dependencies with missing tables",
+            "Ignore dependencies with missing tables or fields",
         );
         ap.refer(&mut options.minimize).add_option(
             &["-m", "--minimize-fds"],
@@ -109,11 +790,31 @@ fn main() {
             StoreOption,
             "A threshold at which to discard FDs (requires --use-stats)",
         );
+        ap.refer(&mut options.min_confidence).add_option(
+            &["--min-confidence"],
+            StoreOption,
+            "The lowest confidence of FDs used for normalization (by default only exact FDs)",
+        );
+        ap.refer(&mut options.keys).add_option(
+            &["--key"],
+            Collect,
+            "Use fields as the primary key of a table (TABLE=FIELD,FIELD)",
+        );
         ap.refer(&mut options.show_dependencies).add_option(
             &["-d", "--show-dependencies"],
             StoreTrue,
             "Display the remaining dependencies on completion",
         );
+        ap.refer(&mut options.check_lossless).add_option(
+            &["--check-lossless"],
+            StoreTrue,
+            "Check that each input table is a lossless join of the output",
+        );
+        ap.refer(&mut options.report_lost_fds).add_option(
+            &["--report-lost-fds"],
+            StoreTrue,
+            "Report input FDs which are not preserved by the output",
+        );
         ap.refer(&mut options.log_level).add_option(
             &["-l", "--log-level"],
             Store,
@@ -124,11 +825,7 @@ fn main() {
 
     // Validate arguments
     if options.fd_threshold.is_some() && !options.use_stats {
-        writeln!(
-            io::stderr(),
-            "Specifying --fd-threshold requires --use-stats"
-        )
-        .unwrap();
+        eprintln!("Specifying --fd-threshold requires --use-stats");
         ::std::process::exit(1);
     }
 
@@ -139,53 +836,11 @@ fn main() {
         .ok();
 
     info!("Loading schema {}", options.input);
-    let input_string = read_file(&options.input).unwrap();
-    let (table_vec, fd_vec, ind_vec, frequencies) = input::input::input(&input_string).unwrap();
-
-    let mut schema = Schema {
-        ..Default::default()
+    let policy = validate::Policy {
+        ignore_missing: options.ignore_missing,
+        use_stats: options.use_stats,
     };
-    // Build a HashMap of parsed Tables
-    for table in table_vec {
-        schema.tables.insert(table.name.clone(), table);
-    }
-
-    // Copy frequencies to the tables and fields
-    for freq in frequencies {
-        let table = schema
-            .tables
-            .get_mut(&freq.0)
-            .expect(&format!("found stats for unknown table {}", freq.0));
-        match freq.1 {
-            Some(field_name) => {
-                let field = table.fields.get_mut(&field_name).expect(&format!(
-                    "found stats for unknown field {} on {}",
-                    field_name, freq.0
-                ));
-                field.cardinality = Some(freq.2);
-                field.cardinality = Some(freq.2);
-                field.max_length = freq.3;
-            }
-            None => table.row_count = Some(freq.2),
-        }
-    }
-
-    // Add the FDs to each table
-    info!("Adding FDs");
-    for fd in &fd_vec {
-        if options.ignore_missing && !schema.tables.contains_key(&fd.0) {
-            continue;
-        }
-
-        let table = schema
-            .tables
-            .get_mut(&fd.0)
-            .expect(&format!("Missing table {} for FD", fd.0));
-        table.add_fd(
-            fd.1.iter().map(|s| s.parse().unwrap()).collect::<Vec<_>>(),
-            fd.2.iter().map(|s| s.parse().unwrap()).collect::<Vec<_>>(),
-        );
-    }
+    let mut schema = load_schema(&options.input, options.input_format, &policy);
 
     // Adjust the primary keys using statistics if desired
     if options.use_stats {
@@ -194,36 +849,44 @@ fn main() {
         }
     }
 
-    // Create a HashMap of INDs from the parsed data
-    info!("Adding INDs");
-    for ind in &ind_vec {
-        let left_table = ind.0.parse().unwrap();
-        let right_table = ind.2.parse().unwrap();
-        if options.ignore_missing
-            && !(schema.tables.contains_key(&left_table)
-                && schema.tables.contains_key(&right_table))
+    // Keys which are given explicitly replace those which were chosen
+    for key in &options.keys {
+        let (table_name, fields) = match key.find('=') {
+            Some(index) => (&key[..index], &key[index + 1..]),
+            None => {
+                eprintln!("error: invalid key {}", key);
+                ::std::process::exit(2);
+            }
+        };
+        let fields = fields
+            .split(',')
+            .map(|f| f.trim().parse().unwrap())
+            .collect::<Vec<_>>();
+        let result = match schema
+            .tables
+            .get_mut(&table_name.parse::<TableName>().unwrap())
         {
-            continue;
-        }
-
-        let lhs = ind.1.iter().map(|s| s.parse().unwrap()).collect::<Vec<_>>();
-        let permutation = permutation::sort(&lhs[..]);
-        let rhs = ind.3.iter().map(|s| s.parse().unwrap()).collect::<Vec<_>>();
-
-        let new_ind = dependencies::IND {
-            left_table: left_table,
-            left_fields: permutation.apply_slice(&lhs[..]),
-            right_table: right_table,
-            right_fields: permutation.apply_slice(&rhs[..]),
+            Some(table) => table.set_key(&fields),
+            None => Err(format!("no table {}", table_name)),
         };
-        schema.add_ind(new_ind);
+        if let Err(e) = result {
+            eprintln!("error: {}: {}", options.input, e);
+            ::std::process::exit(1);
+        }
     }
 
+    // Keep the input tables to describe how decomposed tables can be rebuilt
+    let input_tables = if options.format == OutputFormat::Cql {
+        schema.tables.values().cloned().collect()
+    } else {
+        Vec::new()
+    };
+
     for table in schema.tables.values_mut() {
         if options.minimize {
             table.minimize_fds();
         }
-        table.fds.closure();
+        table.close_fds();
     }
 
     if options.retain_fks {
@@ -233,17 +896,29 @@ fn main() {
     schema.copy_fds();
     schema.ind_closure();
 
+    // The input is only kept when it must be compared with the output
+    let original = if options.check_lossless || options.report_lost_fds {
+        schema.clone()
+    } else {
+        model::Schema::default()
+    };
+
     let normalizer = Normalizer {
         use_stats: options.use_stats,
         fd_threshold: options.fd_threshold,
+        min_confidence: options.min_confidence,
     };
 
+    // Synthesis produces tables in 3NF in a single step
     let mut changed = true;
+    if options.normalize && options.target == NormalForm::Third {
+        normalizer.synthesize(&mut schema);
+    }
     while changed {
         info!("Looping");
         changed = false;
 
-        if options.normalize {
+        if options.normalize && options.target == NormalForm::Bcnf {
             changed = normalizer.normalize(&mut schema) || changed;
         }
 
@@ -252,11 +927,37 @@ fn main() {
         }
     }
 
-    if options.show_dependencies {
-        println!("{}", schema);
-    } else {
-        for table in schema.tables.values() {
-            println!("{}", table);
+    // Warn about tables which can no longer be rebuilt by joins
+    if options.check_lossless {
+        for check in chase::check_lossless(&original, &schema) {
+            if check.is_lossless() {
+                info!("{}", check);
+            } else {
+                eprintln!("warning: {}", check);
+            }
+        }
+    }
+
+    // FDs split across tables must be enforced by applications
+    if options.report_lost_fds {
+        for lost in chase::lost_fds(&original, &schema) {
+            eprintln!("warning: FD not preserved: {}", lost);
+        }
+    }
+
+    match options.format {
+        OutputFormat::Text => {
+            if options.show_dependencies {
+                println!("{}", schema);
+            } else {
+                for table in schema.tables.values() {
+                    println!("{}", table);
+                }
+            }
         }
+        OutputFormat::Eson => print!("{}", input::write_schema(&schema)),
+        OutputFormat::Sql => print!("{}", sql::write_schema(&schema, options.dialect)),
+        OutputFormat::Cql => print!("{}", cql::write_schema(&schema, &input_tables)),
+        OutputFormat::Json => print!("{}", json::write_schema(&schema)),
     }
 }
@@ -6,12 +6,15 @@
 use defaultmap::DefaultHashMap;
 use float_ord::FloatOrd;
 use indexmap::IndexMap;
+use itertools::Itertools;
 
-use crate::dependencies::{FDClosure, FD, IND};
+use crate::dependencies::{
+    candidate_keys, canonical_cover, closure_proof, field_closure, FDClosure, FD, IND,
+};
 use crate::symbols::{FieldName, TableName};
 
 /// A schema encapsulating tables and their dependencies
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct Schema {
     /// Tables keyed by their name
     pub tables: HashMap<TableName, Table>,
@@ -24,7 +27,7 @@ impl fmt::Display for Schema {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for table in self.tables.values() {
             writeln!(f, "{}", table)?;
-            for fd in table.fds.values() {
+            for fd in table.canonical_cover() {
                 writeln!(f, "  {}", fd)?;
             }
             writeln!(f)?;
@@ -211,8 +214,7 @@ pub fn copy_fds(&mut self) {
                     .expect(&format!("Table for RHS of IND {} does not exist", ind));
                 new_fds.extend(
                     right_table
-                        .fds
-                        .values()
+                        .all_fds()
                         .map(|fd| {
                             let fd_lhs = fd.lhs.clone().into_iter().collect::<HashSet<_>>();
                             let fd_rhs = fd.rhs.clone().into_iter().collect::<HashSet<_>>();
@@ -230,7 +232,7 @@ pub fn copy_fds(&mut self) {
                                     .into_iter()
                                     .filter(|f| left_fields.contains(f))
                                     .collect::<Vec<_>>();
-                                Some((ind.left_table.clone(), left_vec, right_vec))
+                                Some((ind.left_table.clone(), left_vec, right_vec, fd.confidence))
                             } else {
                                 None
                             }
@@ -243,7 +245,10 @@ pub fn copy_fds(&mut self) {
 
         // Add any new FDs which were found
         for fd in new_fds {
-            self.tables.get_mut(&fd.0).unwrap().add_fd(fd.1, fd.2);
+            self.tables
+                .get_mut(&fd.0)
+                .unwrap()
+                .add_approximate_fd(fd.1, fd.2, fd.3);
         }
     }
 
@@ -251,7 +256,8 @@ pub fn copy_fds(&mut self) {
     #[cfg(test)]
     fn validate_fds(&self) {
         for table in self.tables.values() {
-            for (key, fd) in table.fds.iter() {
+            let approximate_fds = table.approximate_fds.iter().map(|((lhs, _), fd)| (lhs, fd));
+            for (key, fd) in table.fds.iter().chain(approximate_fds) {
                 // Ensure the key in the hash table is correct
                 let mut lhs = fd.lhs.iter().map(|f| (*f).clone()).collect::<Vec<_>>();
                 lhs.sort();
@@ -336,6 +342,41 @@ pub struct Field {
 
     /// The maximum length of values in this field
     pub max_length: Option<usize>,
+
+    /// The declared type of this field if it is known
+    pub field_type: Option<FieldType>,
+}
+
+/// The type of values stored in a `Field`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    Boolean,
+    SmallInt,
+    Integer,
+    BigInt,
+    Float,
+    Double,
+
+    /// A fixed-point number with an optional precision and scale
+    Decimal(Option<u32>, Option<u32>),
+
+    /// A fixed-length string with an optional length
+    Char(Option<usize>),
+
+    /// A variable-length string with an optional maximum length
+    Varchar(Option<usize>),
+
+    Text,
+    Binary,
+    Date,
+    Time,
+    Timestamp,
+    Uuid,
+    Json,
+
+    /// Any other type given by its name in the source schema
+    Other(String),
 }
 
 impl PartialEq for Field {
@@ -351,7 +392,7 @@ fn hash<H: Hasher>(&self, state: &mut H) {
 }
 
 /// A table, it's field and any intra-table dependencies
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Table {
     /// The name of the table
     pub name: TableName,
@@ -359,11 +400,25 @@ pub struct Table {
     /// All `Field`s in the table keyed by the name
     pub fields: IndexMap<FieldName, Field>,
 
-    /// Functional dependencies keyed by their left-hand side
+    /// Exact functional dependencies keyed by their left-hand side
     pub fds: HashMap<Vec<FieldName>, FD>,
 
+    /// Functional dependencies which hold on a fraction of rows keyed by
+    /// their left-hand side and confidence, without fields the LHS
+    /// determines exactly or with a higher confidence
+    pub approximate_fds: HashMap<(Vec<FieldName>, FloatOrd<f32>), FD>,
+
     /// The number of rows in this table
     pub row_count: Option<usize>,
+
+    /// Fields forming the partition key when loaded from a wide-column store
+    pub partition_key: Vec<FieldName>,
+
+    /// Fields forming the clustering key in order when loaded from a wide-column store
+    pub clustering_key: Vec<FieldName>,
+
+    /// Names of the input tables this table was derived from during normalization
+    pub sources: Vec<TableName>,
 }
 
 impl Default for Table {
@@ -372,7 +427,11 @@ fn default() -> Table {
             name: TableName::from(""),
             fields: IndexMap::new(),
             fds: HashMap::new(),
+            approximate_fds: HashMap::new(),
             row_count: None,
+            partition_key: Vec::new(),
+            clustering_key: Vec::new(),
+            sources: Vec::new(),
         }
     }
 }
@@ -451,72 +510,147 @@ fn get_field_positions(&self, fields: &HashSet<FieldName>) -> (f32, f32) {
         (left, between)
     }
 
-    /// Pick a primary key from the set of FDs
-    pub fn set_primary_key(&mut self, use_stats: bool) {
-        let pk = {
-            let mut pks = self
-                .fds
-                .values()
-                .filter(|fd| fd.lhs.len() + fd.rhs.len() == self.fields.len());
-
-            if use_stats {
-                pks.max_by_key(|fd| {
-                    // Below is taken from https://dx.doi.org/10.5441/002/edbt.2017.31
+    /// Find every minimal set of fields which determines all fields
+    pub fn candidate_keys(&self) -> Vec<Vec<FieldName>> {
+        let fields = self.fields.keys().cloned().collect::<Vec<_>>();
+        candidate_keys(self.all_fds(), &fields)
+    }
 
-                    let length_score = 1.0 / fd.lhs.len() as f32;
+    /// Score a key using the length, size and position of its fields,
+    /// where higher scores are better keys, if all the fields have statistics
+    pub fn key_score(&self, key: &HashSet<FieldName>) -> Option<f32> {
+        // Below is taken from https://dx.doi.org/10.5441/002/edbt.2017.31
 
-                    let total_length: usize = fd
-                        .lhs
-                        .iter()
-                        .map(|f| {
-                            self.fields[f]
-                                .max_length
-                                .expect(&format!("No max length for {} in {}", f, self.name))
-                        })
-                        .sum();
-                    let value_score = 1.0 / (f32::max(1.0, total_length as f32 - 7.0) as f32);
+        let length_score = 1.0 / key.len() as f32;
+
+        let total_length = key
+            .iter()
+            .map(|f| self.fields[f].max_length)
+            .sum::<Option<usize>>()?;
+        let value_score = 1.0 / f32::max(1.0, total_length as f32 - 7.0);
 
-                    // Get the position of each field in the table
-                    let (left, between) = self.get_field_positions(&fd.lhs);
-                    let position_score = 0.5 * (1.0 / (left + 1.0) + 1.0 / (between + 1.0));
+        // Get the position of each field in the table
+        let (left, between) = self.get_field_positions(key);
+        let position_score = 0.5 * (1.0 / (left + 1.0) + 1.0 / (between + 1.0));
 
-                    FloatOrd(length_score + value_score + position_score)
-                })
-                .expect(&format!("No primary key found for {}", self))
-            } else {
-                pks.next()
-                    .expect(&format!("No primary key found for {}", self))
-            }
-        };
+        Some(length_score + value_score + position_score)
+    }
+
+    /// Pick a primary key from the candidate keys, using the one with the
+    /// best score with statistics or otherwise the one with fewest fields
+    ///
+    /// Since candidate keys come from the closure of the FDs, the key may
+    /// not be the LHS of any FD, and the first key with the fewest fields
+    /// is used even when an FD with a larger LHS covers every field. This
+    /// key is also used when any key has fields without statistics.
+    pub fn set_primary_key(&mut self, use_stats: bool) {
+        let keys = self
+            .candidate_keys()
+            .into_iter()
+            .map(|key| key.into_iter().collect::<HashSet<_>>())
+            .collect::<Vec<_>>();
+        let scores = keys
+            .iter()
+            .map(|key| self.key_score(key))
+            .collect::<Option<Vec<_>>>();
+        let pk = match scores {
+            Some(scores) if use_stats => keys
+                .into_iter()
+                .zip(scores)
+                .max_by_key(|&(_, score)| FloatOrd(score))
+                .map(|(key, _)| key),
+            _ => keys.into_iter().next(),
+        }
+        .filter(|key| !key.is_empty())
+        .unwrap_or_else(|| panic!("No primary key found for {}", self));
 
         for field in self.fields.values_mut() {
-            field.key = pk.lhs.contains(&field.name);
+            field.key = pk.contains(&field.name);
         }
+        self.add_pk_fd();
     }
 
-    /// Add a new `FD` to this table
-    pub fn add_fd(&mut self, mut lhs: Vec<FieldName>, mut rhs: Vec<FieldName>) {
+    /// Use a given set of fields as the primary key if they are a superkey
+    pub fn set_key(&mut self, key: &[FieldName]) -> Result<(), String> {
+        if let Some(field) = key.iter().find(|f| !self.fields.contains_key(*f)) {
+            return Err(format!("no field {} in {}", field, self.name));
+        }
+
+        // Approximate FDs do not always hold so only exact FDs give keys
+        let key = key.iter().cloned().collect::<HashSet<_>>();
+        let closure = field_closure(self.fds.values(), &key);
+        if self.fields.keys().any(|f| !closure.contains_key(f)) {
+            return Err(format!(
+                "{} is not a key of {}",
+                key.iter().sorted().join(", "),
+                self.name
+            ));
+        }
+
+        for field in self.fields.values_mut() {
+            field.key = key.contains(&field.name);
+        }
+        self.add_pk_fd();
+
+        Ok(())
+    }
+
+    /// Add a new exact `FD` to this table
+    pub fn add_fd(&mut self, lhs: Vec<FieldName>, rhs: Vec<FieldName>) {
+        self.add_approximate_fd(lhs, rhs, 1.0);
+    }
+
+    /// Add a new `FD` to this table which holds on a fraction of rows
+    pub fn add_approximate_fd(
+        &mut self,
+        mut lhs: Vec<FieldName>,
+        rhs: Vec<FieldName>,
+        confidence: f32,
+    ) {
         lhs.sort();
         lhs.dedup();
 
-        // Merge this FD with others having the same LHS
-        let key = &lhs.to_vec();
-        if self.fds.contains_key(key) {
-            let old_fd = self.fds.remove(key).unwrap();
-            rhs.extend(old_fd.rhs.into_iter());
-        }
+        // Merge this FD with others having the same LHS and confidence so
+        // that no field is weakened by an FD which is less certain
+        let new_fd = || FD {
+            lhs: lhs.iter().cloned().collect(),
+            rhs: HashSet::new(),
+            confidence,
+        };
+        let fd = if confidence >= 1.0 {
+            self.fds.entry(lhs.clone()).or_insert_with(new_fd)
+        } else {
+            self.approximate_fds
+                .entry((lhs.clone(), FloatOrd(confidence)))
+                .or_insert_with(new_fd)
+        };
+        fd.rhs.extend(rhs);
 
-        let left_set = lhs.into_iter().collect::<HashSet<_>>();
-        let right_set = rhs.into_iter().collect::<HashSet<_>>();
+        self.close_fds();
+    }
 
-        self.fds.insert(
-            key.clone(),
-            FD {
-                lhs: left_set,
-                rhs: right_set,
-            },
-        );
+    /// Extend each exact `FD` to the closure of its LHS and remove the
+    /// fields of approximate `FD`s which their LHS determines exactly or
+    /// with a higher confidence
+    pub fn close_fds(&mut self) {
         self.fds.closure();
+        let approximate = self.approximate_fds.values().cloned().collect::<Vec<_>>();
+        for fd in self.approximate_fds.values_mut() {
+            let exact = field_closure(self.fds.values(), &fd.lhs);
+            let (lhs, confidence) = (fd.lhs.clone(), fd.confidence);
+            fd.rhs.retain(|f| {
+                !exact.contains_key(f)
+                    && !approximate.iter().any(|other| {
+                        other.lhs == lhs && other.confidence > confidence && other.rhs.contains(f)
+                    })
+            });
+        }
+        self.approximate_fds.retain(|_, fd| !fd.rhs.is_empty());
+    }
+
+    /// Produce both the exact and approximate `FD`s of this table
+    pub fn all_fds(&self) -> impl Iterator<Item = &FD> + Clone {
+        self.fds.values().chain(self.approximate_fds.values())
     }
 
     /// Check if this table contains a given FD
@@ -531,24 +665,113 @@ pub fn contains_fd(&self, fd: &FD) -> bool {
         }
     }
 
-    /// Copy `FD`s from another given `Table`
+    /// Copy `FD`s from another given `Table` projected onto the fields of
+    /// this table, which are `X -> X+` for sets `X` of fields of this table
+    /// with the closure `X+` taken under the FDs of the other table
+    ///
+    /// Starting from the LHS of each FD of the other table, fields missing
+    /// from this table are replaced by the LHS of an FD determining them,
+    /// so every FD of the projection is implied by one of the sets found.
     pub fn copy_fds(&mut self, other: &Table) {
-        for fd in other.fds.values() {
-            let new_lhs = fd
-                .lhs
-                .clone()
-                .into_iter()
-                .filter(|f| self.fields.contains_key(f))
-                .collect::<Vec<_>>();
-            let new_rhs = fd
-                .rhs
-                .clone()
-                .into_iter()
-                .filter(|f| self.fields.contains_key(f))
-                .collect::<Vec<_>>();
-            if !new_lhs.is_empty() && !new_rhs.is_empty() {
-                self.add_fd(new_lhs, new_rhs);
+        let is_exact = |closure: &HashMap<FieldName, f32>, field: &FieldName| {
+            closure.get(field).is_some_and(|&c| c >= 1.0)
+        };
+
+        let mut pending = other
+            .all_fds()
+            .map(|fd| fd.lhs.iter().cloned().sorted().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut seen = pending.iter().cloned().collect::<HashSet<_>>();
+        let mut keys: Vec<HashSet<FieldName>> = Vec::new();
+        while let Some(fields) = pending.pop() {
+            // Replace a missing field by the LHS of each FD determining it
+            if let Some(missing) = fields.iter().find(|f| !self.fields.contains_key(*f)) {
+                for fd in other.all_fds() {
+                    if !fd.rhs.contains(missing) || fd.lhs.contains(missing) {
+                        continue;
+                    }
+                    let replaced = fields
+                        .iter()
+                        .filter(|f| *f != missing)
+                        .chain(fd.lhs.iter())
+                        .unique()
+                        .sorted()
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    if seen.insert(replaced.clone()) {
+                        pending.push(replaced);
+                    }
+                }
+                continue;
+            }
+
+            // Fields determined exactly by the rest of the set add nothing
+            let mut lhs = fields.into_iter().collect::<HashSet<_>>();
+            for field in lhs.iter().cloned().sorted().collect::<Vec<_>>() {
+                let mut rest = lhs.clone();
+                rest.remove(&field);
+                if !rest.is_empty() && is_exact(&field_closure(other.all_fds(), &rest), &field) {
+                    lhs = rest;
+                }
             }
+            if keys.iter().any(|key| key.is_subset(&lhs)) {
+                continue;
+            }
+
+            // Fields determined exactly are kept apart from the others
+            let closure = field_closure(other.all_fds(), &lhs);
+            let mut confidence = 1.0;
+            let mut exact_rhs = Vec::new();
+            let mut approximate_rhs = Vec::new();
+            for (field, &field_confidence) in &closure {
+                if !self.fields.contains_key(field) || lhs.contains(field) {
+                    continue;
+                }
+                if field_confidence >= 1.0 {
+                    exact_rhs.push(field.clone());
+                } else {
+                    confidence = f32::min(confidence, field_confidence);
+                    approximate_rhs.push(field.clone());
+                }
+            }
+            if self.fields.keys().all(|f| is_exact(&closure, f)) {
+                keys.push(lhs.clone());
+            }
+
+            let new_lhs = lhs.into_iter().collect::<Vec<_>>();
+            if !exact_rhs.is_empty() {
+                self.add_fd(new_lhs.clone(), exact_rhs);
+            }
+            if !approximate_rhs.is_empty() {
+                self.add_approximate_fd(new_lhs, approximate_rhs, confidence);
+            }
+        }
+    }
+
+    /// Copy the partition and clustering keys from another given `Table`
+    /// keeping only those fields which are still keys of this table
+    pub fn copy_key_layout(&mut self, other: &Table) {
+        let keys = self.key_fields();
+        self.partition_key = other
+            .partition_key
+            .iter()
+            .filter(|f| keys.contains(*f))
+            .cloned()
+            .collect();
+        self.clustering_key = other
+            .clustering_key
+            .iter()
+            .filter(|f| keys.contains(*f))
+            .cloned()
+            .collect();
+    }
+
+    /// The names of the input tables this table was derived from
+    pub fn source_names(&self) -> Vec<TableName> {
+        if self.sources.is_empty() {
+            vec![self.name.clone()]
+        } else {
+            self.sources.clone()
         }
     }
 
@@ -566,17 +789,64 @@ pub fn is_superkey(&self, fields: &HashSet<FieldName>) -> bool {
         self.key_fields().is_subset(fields)
     }
 
+    /// Produce the fields determined by a set of fields, excluding the
+    /// fields themselves, along with the confidence of each
+    pub fn determined_fields(&self, fields: &HashSet<FieldName>) -> HashMap<FieldName, f32> {
+        field_closure(self.all_fds(), fields)
+            .into_iter()
+            .filter(|(field, _)| !fields.contains(field))
+            .collect()
+    }
+
+    /// Find the FDs of this table which derive the closure of a set of
+    /// fields in order along with the new fields each of them adds
+    pub fn closure_proof(&self, fields: &HashSet<FieldName>) -> Vec<(&FD, Vec<FieldName>)> {
+        closure_proof(self.all_fds(), fields)
+    }
+
+    /// Compute a canonical cover of the FDs of this table, which is a
+    /// minimal cover where FDs with the same LHS are merged
+    pub fn canonical_cover(&self) -> Vec<FD> {
+        canonical_cover(self.all_fds())
+    }
+
+    /// Check if an `FD` is implied by the FDs of this table with at
+    /// least the confidence of the given `FD`
+    pub fn implies(&self, fd: &FD) -> bool {
+        let closure = field_closure(self.all_fds(), &fd.lhs);
+        fd.rhs
+            .iter()
+            .all(|f| closure.get(f).is_some_and(|&c| c >= fd.confidence))
+    }
+
     /// Check if this table is in BCNF according to its functional dependencies
-    pub fn is_bcnf(&self, skip_keys: bool, fd_threshold: Option<f32>) -> bool {
-        self.violating_fd(skip_keys, fd_threshold).is_none()
+    pub fn is_bcnf(
+        &self,
+        skip_keys: bool,
+        fd_threshold: Option<f32>,
+        min_confidence: Option<f32>,
+    ) -> bool {
+        self.violating_fd(skip_keys, fd_threshold, min_confidence)
+            .is_none()
     }
 
     /// Find a functional dependency which violates BCNF
-    pub fn violating_fd(&self, use_stats: bool, fd_threshold: Option<f32>) -> Option<&FD> {
-        let mut violators = self
-            .fds
-            .values()
-            .filter(|fd| !fd.is_trivial() && !self.is_superkey(&fd.lhs));
+    ///
+    /// FDs with a confidence below the minimum are never used, and only
+    /// exact FDs are used without a minimum. With
+    /// statistics, the FD with the highest score weighted by its confidence
+    /// is used if the score is above the threshold.
+    pub fn violating_fd(
+        &self,
+        use_stats: bool,
+        fd_threshold: Option<f32>,
+        min_confidence: Option<f32>,
+    ) -> Option<&FD> {
+        let mut violators = self.all_fds().filter(|fd| {
+            !fd.is_trivial()
+                && !self.is_superkey(&fd.lhs)
+                && fd.confidence >= min_confidence.unwrap_or(1.0)
+        });
 
         if use_stats {
             let vfd = violators
@@ -605,7 +875,8 @@ pub fn violating_fd(&self, use_stats: bool, fd_threshold: Option<f32>) -> Option
 
                     // TODO: Add duplication score
 
-                    (fd, FloatOrd(length_score + value_score + position_score))
+                    let score = length_score + value_score + position_score;
+                    (fd, FloatOrd(score * fd.confidence))
                 })
                 .max_by_key(|&(_, score)| score);
             match vfd {
@@ -626,13 +897,13 @@ pub fn violating_fd(&self, use_stats: bool, fd_threshold: Option<f32>) -> Option
     /// Prune `FD`s which reference fields which no longer exist
     pub fn prune_fds(&mut self) {
         let fields = self.fields.keys().collect::<HashSet<_>>();
-        for fd in self.fds.values_mut() {
+        let prune = |fd: &mut FD| {
             fd.lhs.retain(|f| fields.contains(&f));
             fd.rhs.retain(|f| fields.contains(&f));
-        }
-
-        self.fds
-            .retain(|_, fd| !fd.lhs.is_empty() && !fd.rhs.is_empty());
+            !fd.lhs.is_empty() && !fd.rhs.is_empty()
+        };
+        self.fds.retain(|_, fd| prune(fd));
+        self.approximate_fds.retain(|_, fd| prune(fd));
     }
 
     /// Minimize the set of functional dependencies such that
@@ -645,7 +916,7 @@ pub fn minimize_fds(&mut self) {
             let reverse = fd.reverse();
             let rhs = fd.rhs.clone().into_iter().collect::<Vec<_>>();
             if self.fds.contains_key(&rhs)
-                && self.fds[&rhs] == reverse
+                && self.fds[&rhs].rhs == reverse.rhs
                 && fd.lhs.len() > reverse.lhs.len()
             {
                 let mut key = fd.lhs.clone().into_iter().collect::<Vec<_>>();
@@ -699,6 +970,7 @@ fn table_add_pk_fd() {
         let fd = FD {
             lhs: field_set!["foo"],
             rhs: field_set!["bar"],
+            confidence: 1.0,
         };
 
         assert!(t.contains_fd(&fd))
@@ -714,7 +986,7 @@ fn table_is_bcnf_yes() {
             }
         );
         add_fd!(t, vec!["foo"], vec!["bar"]);
-        assert!(t.is_bcnf(false, None))
+        assert!(t.is_bcnf(false, None, None))
     }
 
     #[test]
@@ -730,8 +1002,53 @@ fn table_violating_fd_no_stats() {
         let fd = FD {
             lhs: field_set!["bar"],
             rhs: field_set!["foo"],
+            confidence: 1.0,
         };
-        assert_eq!(t.violating_fd(false, None).unwrap(), &fd)
+        assert_eq!(t.violating_fd(false, None, None).unwrap(), &fd)
+    }
+
+    #[test]
+    fn table_violating_fd_confidence() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("foo", true),
+              field!("bar")
+            }
+        );
+        t.add_approximate_fd(field_vec!["bar"], field_vec!["foo"], 0.9);
+        assert!(t.violating_fd(false, None, None).is_none());
+        assert!(t.violating_fd(false, None, Some(0.8)).is_some());
+        assert!(t.violating_fd(false, None, Some(0.95)).is_none());
+    }
+
+    #[test]
+    fn table_violating_fd_mixed_confidence() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("id", true),
+              field!("a"),
+              field!("b"),
+              field!("c")
+            }
+        );
+        t.add_pk_fd();
+        t.add_approximate_fd(field_vec!["id"], field_vec!["a"], 0.9);
+        t.add_fd(field_vec!["a"], field_vec!["b"]);
+        t.add_approximate_fd(field_vec!["a"], field_vec!["c"], 0.9);
+
+        // Approximate FDs do not weaken exact FDs with the same LHS
+        assert!(t.fds[&field_vec!["id"]].is_exact());
+        assert_eq!(t.fds[&field_vec!["a"]].rhs, field_set!["b"]);
+        assert!(!t
+            .approximate_fds
+            .keys()
+            .any(|(lhs, _)| *lhs == field_vec!["id"]));
+        assert_eq!(
+            t.violating_fd(false, None, Some(0.95)).unwrap(),
+            &t.fds[&field_vec!["a"]]
+        );
     }
 
     #[test]
@@ -744,7 +1061,7 @@ fn table_no_violating_fd() {
             }
         );
         add_fd!(t, vec!["foo"], vec!["bar"]);
-        assert!(t.violating_fd(false, None).is_none())
+        assert!(t.violating_fd(false, None, None).is_none())
     }
 
     #[test]
@@ -761,7 +1078,7 @@ fn table_violating_fd_length() {
         add_fd!(t, vec!["bar", "baz"], vec!["quux"]);
         add_fd!(t, vec!["bar"], vec!["baz", "quux"]);
 
-        assert_eq!(t.violating_fd(true, None).unwrap().lhs.len(), 1);
+        assert_eq!(t.violating_fd(true, None, None).unwrap().lhs.len(), 1);
     }
 
     #[test]
@@ -778,7 +1095,7 @@ fn table_violating_fd_value() {
         add_fd!(t, vec!["baz"], vec!["bar", "quux"]);
         add_fd!(t, vec!["bar"], vec!["baz", "quux"]);
 
-        let lhs = &t.violating_fd(true, None).unwrap().lhs;
+        let lhs = &t.violating_fd(true, None, None).unwrap().lhs;
         assert_eq!(*lhs.iter().next().unwrap(), FieldName::from("baz"));
     }
 
@@ -800,7 +1117,7 @@ fn table_violating_fd_position() {
         add_fd!(t, vec!["bar"], vec!["baz", "quux"]);
         add_fd!(t, vec!["qux"], vec!["corge", "garply"]);
 
-        let lhs = &t.violating_fd(true, None).unwrap().lhs;
+        let lhs = &t.violating_fd(true, None, None).unwrap().lhs;
         assert_eq!(*lhs.iter().next().unwrap(), FieldName::from("bar"));
     }
 
@@ -838,6 +1155,7 @@ fn minimize_fds() {
         let minimized = FD {
             lhs: field_set!["foo"],
             rhs: field_set!["bar", "baz"],
+            confidence: 1.0,
         };
         assert_eq!(t.fds.values().collect::<Vec<_>>(), vec![&minimized]);
     }
@@ -854,7 +1172,7 @@ fn table_is_bcnf_no() {
         );
         add_fd!(t, vec!["foo"], vec!["bar"]);
         add_fd!(t, vec!["bar"], vec!["baz"]);
-        assert!(!t.is_bcnf(false, None))
+        assert!(!t.is_bcnf(false, None, None))
     }
 
     #[test]
@@ -874,6 +1192,13 @@ fn table_key_fields() {
     #[test]
     #[should_panic]
     fn table_set_primary_key_invalid() {
+        let mut t = table!("foo");
+
+        t.set_primary_key(false);
+    }
+
+    #[test]
+    fn table_set_primary_key_closure() {
         let mut t = table!(
             "foo",
             fields! {
@@ -882,9 +1207,50 @@ fn table_set_primary_key_invalid() {
               field!("baz")
             }
         );
-        add_fd!(t, vec!["foo"], vec!["bar"]);
+        t.add_fd(field_vec!["foo"], field_vec!["bar"]);
 
+        // No FD has the key on the LHS but it determines every field
         t.set_primary_key(false);
+
+        assert_has_key!(t, field_vec!["foo", "baz"]);
+        assert_eq!(t.fds[&field_vec!["baz", "foo"]].rhs, field_set!["bar"]);
+    }
+
+    #[test]
+    fn table_candidate_keys() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("foo", false, 1, 20),
+              field!("bar"),
+              field!("baz")
+            }
+        );
+        t.add_fd(field_vec!["foo"], field_vec!["bar", "baz"]);
+        t.add_fd(field_vec!["bar", "baz"], field_vec!["foo"]);
+
+        assert_eq!(
+            t.candidate_keys(),
+            vec![field_vec!["foo"], field_vec!["bar", "baz"]]
+        );
+
+        // The short values of the longer key make it score higher
+        let foo_score = t.key_score(&field_set!["foo"]).unwrap();
+        let bar_baz_score = t.key_score(&field_set!["bar", "baz"]).unwrap();
+        assert!(bar_baz_score > foo_score);
+        t.set_primary_key(true);
+        assert_has_key!(t, field_vec!["bar", "baz"]);
+
+        assert!(t.set_key(&[FieldName::from("foo")]).is_ok());
+        assert_has_key!(t, field_vec!["foo"]);
+        assert_eq!(
+            t.set_key(&[FieldName::from("bar")]),
+            Err("bar is not a key of foo".to_string())
+        );
+        assert_eq!(
+            t.set_key(&[FieldName::from("quux")]),
+            Err("no field quux in foo".to_string())
+        );
     }
 
     #[test]
@@ -903,6 +1269,68 @@ fn table_set_primary_key_no_stats() {
         assert_has_key!(t, field_vec!["foo"])
     }
 
+    #[test]
+    fn table_set_primary_key_fewest_fields() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("foo"),
+              field!("bar"),
+              field!("baz")
+            }
+        );
+        t.add_fd(field_vec!["foo", "bar"], field_vec!["baz"]);
+        t.add_fd(field_vec!["baz"], field_vec!["foo", "bar"]);
+
+        // The smaller key is used even though the larger one was added first
+        t.set_primary_key(false);
+
+        assert_has_key!(t, field_vec!["baz"])
+    }
+
+    #[test]
+    fn table_candidate_keys_approximate() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("foo"),
+              field!("bar"),
+              field!("baz")
+            }
+        );
+        t.add_fd(field_vec!["foo", "bar"], field_vec!["baz"]);
+        t.add_approximate_fd(field_vec!["foo"], field_vec!["bar"], 0.9);
+
+        // The approximate FD does not make foo a key
+        assert_eq!(t.candidate_keys(), vec![field_vec!["foo", "bar"]]);
+        assert_eq!(
+            t.set_key(&[FieldName::from("foo")]),
+            Err("foo is not a key of foo".to_string())
+        );
+        assert!(t
+            .set_key(&[FieldName::from("foo"), FieldName::from("bar")])
+            .is_ok());
+    }
+
+    #[test]
+    fn table_set_primary_key_no_lengths() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("foo", false, 1, 20),
+              field!("bar"),
+              field!("baz")
+            }
+        );
+        t.fields[1].max_length = None;
+        t.add_fd(field_vec!["foo"], field_vec!["bar", "baz"]);
+        t.add_fd(field_vec!["bar", "baz"], field_vec!["foo"]);
+
+        // The key with the fewest fields is used without every score
+        t.set_primary_key(true);
+        assert_has_key!(t, field_vec!["foo"]);
+    }
+
     #[test]
     fn table_set_primary_key_length() {
         let mut t = table!(
@@ -997,6 +1425,7 @@ fn table_contains_fd() {
         let fd = FD {
             lhs: field_set!["foo"],
             rhs: field_set!["bar"],
+            confidence: 1.0,
         };
 
         assert!(t1.contains_fd(&fd))
@@ -1015,6 +1444,7 @@ fn table_contains_fd_no() {
         let fd = FD {
             lhs: field_set!["bar"],
             rhs: field_set!["foo"],
+            confidence: 1.0,
         };
 
         assert!(!t1.contains_fd(&fd))
@@ -1044,11 +1474,178 @@ fn table_copy_fds() {
         let copied_fd = FD {
             lhs: field_set!["foo"],
             rhs: field_set!["bar"],
+            confidence: 1.0,
         };
         let copied_fds = t2.fds.values().collect::<Vec<_>>();
         assert_eq!(vec![&copied_fd], copied_fds)
     }
 
+    #[test]
+    fn table_copy_fds_closure() {
+        let mut t1 = table!(
+            "foo",
+            fields! {
+              field!("k", true),
+              field!("a"),
+              field!("b"),
+              field!("c"),
+              field!("d"),
+              field!("e")
+            }
+        );
+        let mut t2 = table!(
+            "foo_base",
+            fields! {
+              field!("k", true),
+              field!("a"),
+              field!("c"),
+              field!("d"),
+              field!("e")
+            }
+        );
+        t1.add_fd(field_vec!["a"], field_vec!["b"]);
+        t1.add_fd(field_vec!["a", "c"], field_vec!["d"]);
+        t1.add_fd(field_vec!["b", "c"], field_vec!["e"]);
+        t2.copy_fds(&t1);
+
+        // The FD on b, c only holds in this table by augmenting a -> b
+        assert_eq!(t2.fds[&field_vec!["a", "c"]].rhs, field_set!["d", "e"]);
+        assert!(!t2.fds.contains_key(&field_vec!["c"]));
+        assert_eq!(t2.fds.len(), 2);
+    }
+
+    #[test]
+    fn table_copy_fds_projection() {
+        let mut t1 = table!(
+            "foo",
+            fields! {
+              field!("k", true),
+              field!("a"),
+              field!("b"),
+              field!("c"),
+              field!("e")
+            }
+        );
+        let mut t2 = table!(
+            "foo_base",
+            fields! {
+              field!("k", true),
+              field!("a"),
+              field!("c"),
+              field!("e")
+            }
+        );
+        t1.add_pk_fd();
+        t1.add_fd(field_vec!["a"], field_vec!["b"]);
+        t1.add_fd(field_vec!["b", "c"], field_vec!["e"]);
+        t2.copy_fds(&t1);
+
+        // No FD of the original table has a, c as the LHS
+        assert_eq!(t2.fds[&field_vec!["a", "c"]].rhs, field_set!["e"]);
+        assert!(!t2.is_bcnf(false, None, None));
+    }
+
+    #[test]
+    fn table_copy_fds_wide() {
+        let mut t1 = table!("foo", fields! { field!("k", true) });
+        let mut t2 = table!("foo_base", fields! { field!("k", true) });
+        let names = |i| {
+            (
+                format!("a{}", i).parse::<FieldName>().unwrap(),
+                format!("b{}", i).parse::<FieldName>().unwrap(),
+            )
+        };
+        for i in 0..32 {
+            let (a, b) = names(i);
+            for name in [&a, &b] {
+                let field = Field {
+                    name: name.clone(),
+                    ..field!("")
+                };
+                t1.fields.insert(name.clone(), field.clone());
+                t2.fields.insert(name.clone(), field);
+            }
+            t1.add_fd(vec![a], vec![b]);
+        }
+        t2.copy_fds(&t1);
+
+        // Sets of fields from different FDs are never tried together
+        assert_eq!(t2.fds.len(), 32);
+        for i in 0..32 {
+            let (a, b) = names(i);
+            assert_eq!(t2.fds[&vec![a]].rhs, collect![as HashSet<_>: b]);
+        }
+    }
+
+    #[test]
+    fn table_closure() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("foo", true),
+              field!("bar"),
+              field!("baz"),
+              field!("quux")
+            }
+        );
+        t.add_fd(field_vec!["bar"], field_vec!["baz"]);
+        t.add_fd(field_vec!["baz"], field_vec!["quux"]);
+        t.add_approximate_fd(field_vec!["quux"], field_vec!["bar"], 0.9);
+
+        assert_eq!(
+            t.determined_fields(&field_set!["bar"]),
+            collect![FieldName::from("baz") => 1.0, FieldName::from("quux") => 1.0]
+        );
+        assert_eq!(
+            t.determined_fields(&field_set!["quux"]),
+            collect![FieldName::from("bar") => 0.9, FieldName::from("baz") => 0.9]
+        );
+        assert!(t.implies(&FD {
+            lhs: field_set!["bar", "foo"],
+            rhs: field_set!["quux"],
+            confidence: 1.0,
+        }));
+        assert!(!t.implies(&FD {
+            lhs: field_set!["quux"],
+            rhs: field_set!["bar"],
+            confidence: 1.0,
+        }));
+        assert!(t.implies(&FD {
+            lhs: field_set!["quux"],
+            rhs: field_set!["bar"],
+            confidence: 0.8,
+        }));
+        assert!(!t.implies(&FD {
+            lhs: field_set!["baz"],
+            rhs: field_set!["foo"],
+            confidence: 1.0,
+        }));
+    }
+
+    #[test]
+    fn table_canonical_cover() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("foo", true),
+              field!("bar"),
+              field!("baz")
+            }
+        );
+        t.add_fd(field_vec!["bar"], field_vec!["foo"]);
+        t.add_fd(field_vec!["bar", "baz"], field_vec!["foo"]);
+
+        // The closure adds every field to the RHS of each FD
+        assert_eq!(t.fds.len(), 3);
+
+        let cover = t.canonical_cover();
+        assert_eq!(cover.len(), 2);
+        assert_eq!(cover[0].lhs, field_set!["bar"]);
+        assert_eq!(cover[0].rhs, field_set!["foo"]);
+        assert_eq!(cover[1].lhs, field_set!["foo"]);
+        assert_eq!(cover[1].rhs, field_set!["bar", "baz"]);
+    }
+
     #[test]
     fn schema_add_ind_subset() {
         let t1 = table!(
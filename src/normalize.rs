@@ -1,17 +1,39 @@
 use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 
 use indexmap::IndexMap;
+use itertools::Itertools;
 
-#[cfg(test)]
-use crate::dependencies::FD;
-
-use crate::dependencies::IND;
+use crate::dependencies::{field_closure, minimal_cover, FD, IND};
 use crate::model::{Field, Schema, Table};
 use crate::symbols::{FieldName, TableName};
 
+/// The normal forms which tables can be normalized to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalForm {
+    /// Boyce-Codd normal form by repeatedly decomposing tables
+    Bcnf,
+
+    /// Third normal form by synthesizing tables from a minimal cover
+    Third,
+}
+
+impl FromStr for NormalForm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<NormalForm, String> {
+        match s.to_lowercase().as_str() {
+            "bcnf" => Ok(NormalForm::Bcnf),
+            "3nf" => Ok(NormalForm::Third),
+            _ => Err(format!("unknown normal form {}", s)),
+        }
+    }
+}
+
 pub struct Normalizer {
     pub use_stats: bool,
     pub fd_threshold: Option<f32>,
+    pub min_confidence: Option<f32>,
 }
 
 impl Normalizer {
@@ -24,35 +46,33 @@ fn decomposed_tables(
         let t = tables.get(&table_name).unwrap();
 
         // Find a violating FD
-        let vfd = t.violating_fd(self.use_stats, self.fd_threshold).unwrap();
+        let vfd = t
+            .violating_fd(self.use_stats, self.fd_threshold, self.min_confidence)
+            .unwrap();
 
         debug!("Decomposing {} because of {}", t, vfd);
 
-        // Construct t1 with only fields from the FD
+        // Construct t1 with only fields from the FD, where the LHS joins
+        // the key if key fields on the RHS are removed since the rest of
+        // the key no longer determines the table
+        let replace_key = t
+            .fields
+            .values()
+            .any(|f| f.key && vfd.rhs.contains(&f.name));
         let t1_fields = t
             .fields
             .clone()
             .into_iter()
             .filter(|&(ref k, _)| !vfd.rhs.contains(k))
             .map(|(k, v)| {
-                (
-                    k,
-                    if v.key && vfd.rhs.contains(&v.name) {
-                        Field {
-                            name: v.name,
-                            key: false,
-                            cardinality: v.cardinality,
-                            max_length: v.max_length,
-                        }
-                    } else {
-                        v
-                    },
-                )
+                let key = v.key || (replace_key && vfd.lhs.contains(&k));
+                (k, Field { key, ..v })
             })
             .collect::<IndexMap<FieldName, Field>>();
         let mut t1 = Table {
             name: (t.name.to_string().clone() + "_base").parse().unwrap(),
             fields: t1_fields,
+            sources: t.source_names(),
             ..Default::default()
         };
         t1.add_pk_fd();
@@ -73,6 +93,7 @@ fn decomposed_tables(
                             key: true,
                             cardinality: v.cardinality,
                             max_length: v.max_length,
+                            field_type: v.field_type,
                         }
                     } else if v.key && !vfd.lhs.contains(&v.name) {
                         Field {
@@ -80,6 +101,7 @@ fn decomposed_tables(
                             key: false,
                             cardinality: v.cardinality,
                             max_length: v.max_length,
+                            field_type: v.field_type,
                         }
                     } else {
                         v
@@ -90,6 +112,7 @@ fn decomposed_tables(
         let mut t2 = Table {
             name: (t.name.to_string().clone() + "_ext").parse().unwrap(),
             fields: t2_fields,
+            sources: t.source_names(),
             ..Default::default()
         };
         t2.add_pk_fd();
@@ -100,6 +123,9 @@ fn decomposed_tables(
             t2.set_primary_key(true);
         }
 
+        t1.copy_key_layout(t);
+        t2.copy_key_layout(t);
+
         (t1, t2)
     }
 
@@ -121,7 +147,7 @@ pub fn normalize(&self, schema: &mut Schema) -> bool {
                 // Skip tables already in BCNF
                 {
                     let t = &schema.tables[&table_name];
-                    if t.is_bcnf(self.use_stats, self.fd_threshold) {
+                    if t.is_bcnf(self.use_stats, self.fd_threshold, self.min_confidence) {
                         continue;
                     }
                 }
@@ -172,6 +198,155 @@ pub fn normalize(&self, schema: &mut Schema) -> bool {
         any_changed
     }
 
+    /// Synthesize tables in 3NF from a minimal cover of the FDs of a
+    /// table, producing no tables if the table would be unchanged
+    ///
+    /// FDs with at least the minimum confidence are treated as exact
+    /// since the tables are only built from the FDs which are kept, and
+    /// only exact FDs are kept without a minimum.
+    fn synthesized_tables(&self, t: &Table) -> Vec<Table> {
+        let fds = t
+            .all_fds()
+            .filter(|fd| fd.confidence >= self.min_confidence.unwrap_or(1.0))
+            .map(|fd| FD {
+                confidence: 1.0,
+                ..fd.clone()
+            })
+            .collect::<Vec<_>>();
+        let cover = minimal_cover(&fds);
+
+        // Each group of FDs with the same LHS becomes a table keyed by the LHS
+        let mut groups: IndexMap<Vec<FieldName>, HashSet<FieldName>> = IndexMap::new();
+        for fd in &cover {
+            let lhs = fd.lhs.iter().cloned().sorted().collect::<Vec<_>>();
+            let fields = groups.entry(lhs).or_default();
+            fields.extend(fd.lhs.iter().cloned());
+            fields.extend(fd.rhs.iter().cloned());
+        }
+        let mut schemes = groups.into_iter().collect::<Vec<_>>();
+
+        // Tables with fields contained in another table are not needed
+        let contained = (0..schemes.len())
+            .filter(|&i| {
+                schemes.iter().enumerate().any(|(j, other)| {
+                    schemes[i].1.is_subset(&other.1)
+                        && (schemes[i].1.len() < other.1.len() || j < i)
+                })
+            })
+            .collect::<Vec<_>>();
+        for i in contained.into_iter().rev() {
+            schemes.remove(i);
+        }
+
+        // Add a table for a key if no table contains one, reducing the
+        // existing key or all fields to a minimal set of fields
+        let all_fields = t.fields.keys().cloned().collect::<HashSet<_>>();
+        let is_superkey =
+            |fields: &HashSet<FieldName>| field_closure(&cover, fields).len() == all_fields.len();
+        if !schemes.iter().any(|(_, fields)| is_superkey(fields)) {
+            let mut key = t.key_fields();
+            if key.is_empty() || !is_superkey(&key) {
+                key = all_fields.clone();
+            }
+            for field in key.iter().cloned().sorted().collect::<Vec<_>>() {
+                key.remove(&field);
+                if !is_superkey(&key) {
+                    key.insert(field);
+                }
+            }
+            schemes.push((key.iter().cloned().sorted().collect(), key));
+        }
+
+        if schemes.len() <= 1 {
+            return Vec::new();
+        }
+
+        schemes
+            .into_iter()
+            .map(|(key, fields)| {
+                let mut table = Table {
+                    name: format!("{}_{}", t.name, key.iter().join("_"))
+                        .parse()
+                        .unwrap(),
+                    fields: t
+                        .fields
+                        .values()
+                        .filter(|f| fields.contains(&f.name))
+                        .map(|f| {
+                            (
+                                f.name.clone(),
+                                Field {
+                                    key: key.contains(&f.name),
+                                    ..f.clone()
+                                },
+                            )
+                        })
+                        .collect(),
+                    sources: t.source_names(),
+                    ..Default::default()
+                };
+                table.add_pk_fd();
+                table.copy_fds(t);
+                table.copy_key_layout(t);
+
+                table
+            })
+            .collect()
+    }
+
+    /// Perform 3NF synthesis on tables in a schema
+    pub fn synthesize(&self, schema: &mut Schema) -> bool {
+        let mut any_changed = false;
+
+        let table_names = schema.tables.keys().cloned().sorted().collect::<Vec<_>>();
+        for table_name in table_names {
+            let tables = self.synthesized_tables(&schema.tables[&table_name]);
+            if tables.is_empty() {
+                continue;
+            }
+            any_changed = true;
+            debug!(
+                "Synthesized {} from {}",
+                tables.iter().join(", "),
+                table_name
+            );
+
+            // Link each table to the tables whose key it contains
+            for t1 in &tables {
+                for t2 in &tables {
+                    let key = t2.key_fields();
+                    if t1.name == t2.name || !key.iter().all(|f| t1.fields.contains_key(f)) {
+                        continue;
+                    }
+
+                    let ind_fields = key.into_iter().sorted().collect::<Vec<_>>();
+                    let ind = IND {
+                        left_table: t1.name.clone(),
+                        left_fields: ind_fields.clone(),
+                        right_table: t2.name.clone(),
+                        right_fields: ind_fields,
+                    };
+                    debug!("Adding INDs {} and {}", ind, ind.reverse());
+                    schema.add_ind(ind.reverse());
+                    schema.add_ind(ind);
+                }
+            }
+
+            let names = tables.iter().map(|t| t.name.clone()).collect::<Vec<_>>();
+            for table in tables {
+                schema.tables.insert(table.name.clone(), table);
+            }
+            for name in &names {
+                schema.copy_inds(&table_name, name);
+            }
+
+            schema.tables.remove(&table_name);
+            schema.prune_inds();
+        }
+
+        any_changed
+    }
+
     /// Perform subsumption of tables in a Schema based on INDs
     pub fn subsume(&self, schema: &mut Schema) -> bool {
         let mut any_changed = false;
@@ -343,19 +518,25 @@ pub fn subsume(&self, schema: &mut Schema) -> bool {
 
                     if keys_match && schema.contains_ind(&ind.reverse()) {
                         // Copy the fields and FDs from the left table into a new table
+                        let mut sources = left_table.source_names();
+                        sources.extend(right_table.source_names());
+                        sources.sort();
+                        sources.dedup();
                         let mut new_table = Table {
                             name: format!("{}_{}", left_table.name, right_table.name)
                                 .parse()
                                 .unwrap(),
+                            sources,
                             ..Default::default()
                         };
                         for (name, field) in &left_table.fields {
                             new_table.fields.insert(name.clone(), field.clone());
                         }
-                        for fd in left_table.fds.values() {
-                            new_table.add_fd(
+                        for fd in left_table.all_fds() {
+                            new_table.add_approximate_fd(
                                 fd.lhs.iter().cloned().collect::<Vec<_>>(),
                                 fd.rhs.iter().cloned().collect::<Vec<_>>(),
+                                fd.confidence,
                             );
                         }
 
@@ -388,11 +569,12 @@ pub fn subsume(&self, schema: &mut Schema) -> bool {
                                     key: field.key,
                                     cardinality: field.cardinality,
                                     max_length: field.max_length,
+                                    field_type: field.field_type.clone(),
                                 },
                             );
                         }
-                        for fd in right_table.fds.values() {
-                            new_table.add_fd(
+                        for fd in right_table.all_fds() {
+                            new_table.add_approximate_fd(
                                 fd.lhs
                                     .iter()
                                     .map(|f| new_right_names[f].clone())
@@ -401,10 +583,26 @@ pub fn subsume(&self, schema: &mut Schema) -> bool {
                                     .iter()
                                     .map(|f| new_right_names[f].clone())
                                     .collect::<Vec<_>>(),
+                                fd.confidence,
                             );
                         }
                         new_table.add_pk_fd();
 
+                        // Keys are shared, so the layout of either table can be used
+                        new_table.copy_key_layout(left_table);
+                        if new_table.partition_key.is_empty() {
+                            new_table.partition_key = right_table
+                                .partition_key
+                                .iter()
+                                .filter_map(|f| new_right_names.get(f).cloned())
+                                .collect();
+                            new_table.clustering_key = right_table
+                                .clustering_key
+                                .iter()
+                                .filter_map(|f| new_right_names.get(f).cloned())
+                                .collect();
+                        }
+
                         any_changed = true;
                         new_tables.push((
                             new_table,
@@ -459,6 +657,7 @@ fn normalize() {
         let normalizer = Normalizer {
             use_stats: false,
             fd_threshold: None,
+            min_confidence: None,
         };
         normalizer.normalize(&mut schema);
         schema.validate();
@@ -489,6 +688,7 @@ fn normalize_change_keys() {
         let normalizer = Normalizer {
             use_stats: false,
             fd_threshold: None,
+            min_confidence: None,
         };
         normalizer.normalize(&mut schema);
         schema.validate();
@@ -502,6 +702,171 @@ fn normalize_change_keys() {
         assert_has_fields!(t2, field_vec!["foo", "bar", "baz"]);
     }
 
+    #[test]
+    fn normalize_key_on_rhs() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("foo", true),
+              field!("bar", true),
+              field!("baz")
+            }
+        );
+        t.add_fd(field_vec!["baz"], field_vec!["bar"]);
+        let mut schema = schema! {t};
+
+        schema.validate();
+        let normalizer = Normalizer {
+            use_stats: false,
+            fd_threshold: None,
+            min_confidence: None,
+        };
+        normalizer.normalize(&mut schema);
+        schema.validate();
+
+        // foo alone does not determine baz once bar is removed
+        let t1 = schema.tables.get(&TableName::from("foo_base")).unwrap();
+        assert_has_key!(t1, field_vec!["foo", "baz"]);
+        assert_has_fields!(t1, field_vec!["foo", "baz"]);
+
+        let t2 = schema.tables.get(&TableName::from("foo_ext")).unwrap();
+        assert_has_key!(t2, field_vec!["baz"]);
+        assert_has_fields!(t2, field_vec!["bar", "baz"]);
+    }
+
+    #[test]
+    fn normalize_projected_fd() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("k", true),
+              field!("a"),
+              field!("b"),
+              field!("c"),
+              field!("e")
+            }
+        );
+        t.add_fd(field_vec!["a"], field_vec!["b"]);
+        t.add_fd(field_vec!["b", "c"], field_vec!["e"]);
+        let mut schema = schema! {t};
+
+        schema.validate();
+        let normalizer = Normalizer {
+            use_stats: false,
+            fd_threshold: None,
+            min_confidence: None,
+        };
+        normalizer.normalize(&mut schema);
+        schema.validate();
+
+        // Every table is in BCNF whichever FD is used first
+        for table in schema.tables.values() {
+            assert!(table.is_bcnf(false, None, None), "{}", table);
+        }
+    }
+
+    #[test]
+    fn synthesize() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("foo", true),
+              field!("bar"),
+              field!("baz"),
+              field!("quux")
+            }
+        );
+        t.add_fd(field_vec!["bar"], field_vec!["baz"]);
+        let mut schema = schema! {t};
+
+        schema.validate();
+        let normalizer = Normalizer {
+            use_stats: false,
+            fd_threshold: None,
+            min_confidence: None,
+        };
+        assert!(normalizer.synthesize(&mut schema));
+        schema.validate();
+        assert_eq!(schema.tables.len(), 2);
+
+        let t1 = schema.tables.get(&TableName::from("foo_foo")).unwrap();
+        assert_has_key!(t1, field_vec!["foo"]);
+        assert_has_fields!(t1, field_vec!["foo", "bar", "quux"]);
+
+        let t2 = schema.tables.get(&TableName::from("foo_bar")).unwrap();
+        assert_has_key!(t2, field_vec!["bar"]);
+        assert_has_fields!(t2, field_vec!["bar", "baz"]);
+
+        assert!(schema.contains_ind(&IND {
+            left_table: TableName::from("foo_foo"),
+            left_fields: field_vec!["bar"],
+            right_table: TableName::from("foo_bar"),
+            right_fields: field_vec!["bar"],
+        }));
+
+        // Synthesized tables are already in 3NF
+        assert!(!normalizer.synthesize(&mut schema));
+    }
+
+    #[test]
+    fn synthesize_key_table() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("foo", true),
+              field!("bar", true),
+              field!("baz")
+            }
+        );
+        t.add_fd(field_vec!["foo"], field_vec!["baz"]);
+        let mut schema = schema! {t};
+
+        let normalizer = Normalizer {
+            use_stats: false,
+            fd_threshold: None,
+            min_confidence: None,
+        };
+        assert!(normalizer.synthesize(&mut schema));
+        schema.validate();
+        assert_eq!(schema.tables.len(), 2);
+
+        let t1 = schema.tables.get(&TableName::from("foo_foo")).unwrap();
+        assert_has_key!(t1, field_vec!["foo"]);
+        assert_has_fields!(t1, field_vec!["foo", "baz"]);
+
+        // No FD determines the other fields so the key is its own table
+        let t2 = schema.tables.get(&TableName::from("foo_bar_foo")).unwrap();
+        assert_has_key!(t2, field_vec!["foo", "bar"]);
+        assert_has_fields!(t2, field_vec!["foo", "bar"]);
+    }
+
+    #[test]
+    fn normalize_approximate() {
+        let mut t = table!(
+            "foo",
+            fields! {
+              field!("foo", true),
+              field!("bar"),
+              field!("baz")
+            }
+        );
+        t.add_approximate_fd(field_vec!["bar"], field_vec!["baz"], 0.9);
+        let schema = schema! {t};
+
+        // Only exact FDs are used without a minimum confidence
+        let mut normalizer = Normalizer {
+            use_stats: false,
+            fd_threshold: None,
+            min_confidence: None,
+        };
+        assert!(!normalizer.normalize(&mut schema.clone()));
+        assert!(!normalizer.synthesize(&mut schema.clone()));
+
+        normalizer.min_confidence = Some(0.8);
+        assert!(normalizer.normalize(&mut schema.clone()));
+        assert!(normalizer.synthesize(&mut schema.clone()));
+    }
+
     #[test]
     fn subsume_fields() {
         let t1 = table!(
@@ -534,6 +899,7 @@ fn subsume_fields() {
         let normalizer = Normalizer {
             use_stats: false,
             fd_threshold: None,
+            min_confidence: None,
         };
         assert!(normalizer.subsume(&mut schema));
         schema.validate();
@@ -582,6 +948,7 @@ fn subsume_table() {
         let normalizer = Normalizer {
             use_stats: false,
             fd_threshold: None,
+            min_confidence: None,
         };
         assert!(normalizer.subsume(&mut schema));
         schema.validate();
@@ -616,6 +983,7 @@ fn subsume_merge() {
         let normalizer = Normalizer {
             use_stats: false,
             fd_threshold: None,
+            min_confidence: None,
         };
         assert!(normalizer.subsume(&mut schema));
         schema.validate();
@@ -627,6 +995,7 @@ fn subsume_merge() {
         let fd = FD {
             lhs: field_set!["bar"],
             rhs: field_set!["corge"],
+            confidence: 1.0,
         };
         assert!(table.contains_fd(&fd));
     }
@@ -0,0 +1,958 @@
+extern crate peg;
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use indexmap::IndexMap;
+use itertools::Itertools;
+
+use crate::dependencies::IND;
+use crate::input::Input;
+use crate::model::{Field, FieldType, Schema, Table};
+use crate::symbols::TableName;
+
+/// A lexical token inside a table definition
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// An unquoted word such as a keyword, identifier or number
+    Word(String),
+
+    /// A quoted identifier
+    Quoted(String),
+
+    /// Tokens enclosed in parentheses
+    Group(Vec<Token>),
+
+    /// Any other symbol or literal
+    Other(String),
+}
+
+/// A statement which is relevant to building a schema
+pub enum Statement {
+    /// A table name with the tokens of each column or constraint definition
+    Table(String, Vec<Vec<Token>>),
+
+    /// A unique index given by a table name and the indexed columns
+    UniqueIndex(String, Token),
+}
+
+peg::parser! {
+  pub grammar sql() for str {
+    rule comment()
+      = "--" [^'\n']* / "/*" (!"*/" [_])* "*/"
+
+    rule _()
+      = quiet!{([' ' | '\t' | '\r' | '\n'] / comment())*}
+
+    rule kw(k: &'static str)
+      = w:$(['a'..='z' | 'A'..='Z' | '_']+) {? if w.eq_ignore_ascii_case(k) { Ok(()) } else { Err(k) } }
+
+    rule string()
+      = "'" ("''" / [^'\''])* "'" / "$$" (!"$$" [_])* "$$"
+
+    rule identifier() -> String
+      = "\"" s:$([^'"']*) "\"" { s.to_string() }
+      / "`" s:$([^'`']*) "`" { s.to_string() }
+      / s:$(['a'..='z' | 'A'..='Z' | '_']['a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '$']*) { s.to_string() }
+
+    // A possibly qualified name, keeping only the final component
+    rule name() -> String
+      = names:(identifier() ++ (_ "." _)) { names.last().unwrap().clone() }
+
+    rule token() -> Token
+      = "(" _ tokens:(inner_token() ** _) _ ")" { Token::Group(tokens) }
+      / string() { Token::Other("'".to_string()) }
+      / "\"" s:$([^'"']*) "\"" { Token::Quoted(s.to_string()) }
+      / "`" s:$([^'`']*) "`" { Token::Quoted(s.to_string()) }
+      / w:$(['a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '$']+) { Token::Word(w.to_string()) }
+      / c:$([^ ',' | '(' | ')' | ';' | ' ' | '\t' | '\r' | '\n']) { Token::Other(c.to_string()) }
+
+    rule inner_token() -> Token
+      = token() / "," { Token::Other(",".to_string()) }
+
+    rule element() -> Vec<Token>
+      = token() ++ _
+
+    rule create_table_prefix()
+      = kw("create") _ ((kw("temporary") / kw("temp")) _)? kw("table") _
+        (kw("if") _ kw("not") _ kw("exists") _)?
+
+    rule create_table() -> Option<Statement>
+      = create_table_prefix() table:name() _
+        "(" _ elements:(element() ++ (_ "," _)) _ ")" _ (token() ** _) {
+          Some(Statement::Table(table, elements))
+        }
+
+    rule create_unique_index() -> Option<Statement>
+      = kw("create") _ kw("unique") _ kw("index") _ (kw("if") _ kw("not") _ kw("exists") _)?
+        name() _ kw("on") _ table:name() _ (kw("using") _ identifier() _)?
+        columns:token() _ where_clause:(kw("where") (_ token())*)? {
+          // Partial indexes do not imply uniqueness across the whole table
+          match where_clause {
+            Some(_) => None,
+            None => Some(Statement::UniqueIndex(table, columns)),
+          }
+        }
+
+    // Any other statement is ignored, but tables must be parsed
+    rule other()
+      = !create_table_prefix() (string() / comment() / [^';'])+
+      / create_table_prefix() name() _ kw("as") (string() / comment() / [^';'])*
+
+    rule statement() -> Option<Statement>
+      = create_table() / create_unique_index() / other() { None }
+
+    pub rule script() -> Vec<Statement>
+      = _ statements:(s:statement()? _ ";" _ { s })* last:statement()? _ {
+          statements.into_iter().chain(Some(last)).flatten().flatten().collect()
+        }
+  }
+}
+
+/// Check if a token is the given keyword
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    match token {
+        Some(Token::Word(w)) => w.eq_ignore_ascii_case(keyword),
+        _ => false,
+    }
+}
+
+/// Get the identifier named by a token
+fn identifier(token: Option<&Token>) -> Option<String> {
+    match token {
+        Some(Token::Word(w)) | Some(Token::Quoted(w)) => Some(w.clone()),
+        _ => None,
+    }
+}
+
+/// Read a possibly qualified name starting at `i`, keeping the final component
+fn read_name(tokens: &[Token], i: &mut usize) -> Option<String> {
+    let mut name = identifier(tokens.get(*i))?;
+    *i += 1;
+    while tokens.get(*i) == Some(&Token::Other(".".to_string())) {
+        name = identifier(tokens.get(*i + 1))?;
+        *i += 2;
+    }
+
+    Some(name)
+}
+
+/// Get the names of the columns in a parenthesized list, returning `None`
+/// if any entry is an expression instead of a column
+fn column_names(token: Option<&Token>) -> Option<Vec<String>> {
+    let tokens = match token {
+        Some(Token::Group(tokens)) => tokens,
+        _ => return None,
+    };
+
+    tokens
+        .split(|t| *t == Token::Other(",".to_string()))
+        .map(|entry| {
+            let name = identifier(entry.first())?;
+            match entry.get(1) {
+                // Allow a prefix length on the column as in MySQL
+                Some(Token::Group(length)) => match &length[..] {
+                    [Token::Word(w)] if w.chars().all(|c| c.is_ascii_digit()) => Some(name),
+                    _ => None,
+                },
+                _ => Some(name),
+            }
+        })
+        .collect()
+}
+
+/// Check if the word at position `i` starts a column constraint
+fn starts_constraint(tokens: &[Token], i: usize) -> bool {
+    let word = match tokens.get(i) {
+        Some(Token::Word(w)) => w.to_lowercase(),
+        _ => return true,
+    };
+
+    match word.as_str() {
+        "not" | "null" | "primary" | "unique" | "default" | "references" | "check"
+        | "constraint" | "collate" | "auto_increment" | "autoincrement" | "generated"
+        | "identity" | "comment" | "on" | "as" | "charset" | "key" => true,
+        "character" => is_keyword(tokens.get(i + 1), "set"),
+        _ => false,
+    }
+}
+
+/// Convert the words and arguments of a declared SQL type into a `FieldType`
+pub fn field_type(words: &[String], args: &[u32]) -> FieldType {
+    let words = words
+        .iter()
+        .map(|w| w.to_lowercase())
+        .filter(|w| w != "unsigned" && w != "signed" && w != "zerofill")
+        .collect::<Vec<_>>();
+    let length = args.first().map(|&l| l as usize);
+
+    match words.first().map(|w| w.as_str()).unwrap_or("") {
+        "bool" | "boolean" => FieldType::Boolean,
+        "tinyint" | "smallint" | "int2" | "smallserial" => FieldType::SmallInt,
+        "int" | "integer" | "int4" | "mediumint" | "serial" => FieldType::Integer,
+        "bigint" | "int8" | "bigserial" => FieldType::BigInt,
+        "real" | "float" | "float4" => FieldType::Float,
+        "double" | "float8" => FieldType::Double,
+        "decimal" | "numeric" | "dec" | "number" => {
+            FieldType::Decimal(args.first().cloned(), args.get(1).cloned())
+        }
+        "char" | "character" | "nchar" => {
+            if words.iter().any(|w| w == "varying") {
+                FieldType::Varchar(length)
+            } else {
+                FieldType::Char(length)
+            }
+        }
+        "varchar" | "nvarchar" | "varchar2" | "nvarchar2" => FieldType::Varchar(length),
+        "text" | "tinytext" | "mediumtext" | "longtext" | "clob" | "string" => FieldType::Text,
+        "blob" | "tinyblob" | "mediumblob" | "longblob" | "bytea" | "binary" | "varbinary" => {
+            FieldType::Binary
+        }
+        "date" => FieldType::Date,
+        "time" | "timetz" => FieldType::Time,
+        "timestamp" | "timestamptz" | "datetime" => FieldType::Timestamp,
+        "uuid" => FieldType::Uuid,
+        "json" | "jsonb" => FieldType::Json,
+        _ => FieldType::Other(words.join(" ")),
+    }
+}
+
+/// A table being built from its definition
+struct TableDefinition {
+    table: Table,
+    primary_key: Vec<String>,
+    unique: Vec<Vec<String>>,
+    foreign_keys: Vec<(Vec<String>, String, Option<Vec<String>>)>,
+}
+
+impl TableDefinition {
+    /// Read a foreign key target (a table and optional columns) at position `i`
+    fn read_reference(tokens: &[Token], i: usize) -> Option<(String, Option<Vec<String>>)> {
+        let mut i = i;
+        let table = read_name(tokens, &mut i)?;
+        Some((table, column_names(tokens.get(i))))
+    }
+
+    /// Add a table constraint, returning false if this is not a constraint
+    fn add_constraint(&mut self, tokens: &[Token]) -> bool {
+        let mut i = 0;
+        if is_keyword(tokens.first(), "constraint") {
+            i = 2;
+        }
+
+        if is_keyword(tokens.get(i), "primary") && is_keyword(tokens.get(i + 1), "key") {
+            if let Some(columns) = column_names(tokens.get(i + 2)) {
+                self.primary_key = columns;
+            }
+        } else if is_keyword(tokens.get(i), "unique") {
+            // Skip the optional KEY or INDEX keyword and index name
+            if let Some(columns) = tokens[i + 1..]
+                .iter()
+                .find(|t| matches!(t, Token::Group(_)))
+                .and_then(|t| column_names(Some(t)))
+            {
+                self.unique.push(columns);
+            }
+        } else if is_keyword(tokens.get(i), "foreign") && is_keyword(tokens.get(i + 1), "key") {
+            let group = tokens[i + 2..]
+                .iter()
+                .position(|t| matches!(t, Token::Group(_)))
+                .map(|p| p + i + 2);
+            if let Some(group) = group {
+                if let (Some(columns), true) = (
+                    column_names(tokens.get(group)),
+                    is_keyword(tokens.get(group + 1), "references"),
+                ) {
+                    if let Some((table, references)) = Self::read_reference(tokens, group + 2) {
+                        self.foreign_keys.push((columns, table, references));
+                    }
+                }
+            }
+        } else {
+            return [
+                "constraint",
+                "key",
+                "index",
+                "check",
+                "fulltext",
+                "spatial",
+                "exclude",
+            ]
+            .iter()
+            .any(|k| is_keyword(tokens.first(), k));
+        }
+
+        true
+    }
+
+    /// Add a column and any constraints given with it
+    fn add_column(&mut self, tokens: &[Token]) {
+        let name = match identifier(tokens.first()) {
+            Some(name) => name,
+            None => return,
+        };
+
+        // Collect the words and arguments making up the type
+        let mut i = 1;
+        let mut words = Vec::new();
+        let mut args = Vec::new();
+        while i < tokens.len() {
+            match tokens[i] {
+                Token::Word(ref w) if !starts_constraint(tokens, i) => words.push(w.clone()),
+                Token::Group(ref group) if !words.is_empty() => {
+                    args = group
+                        .iter()
+                        .filter_map(|t| match t {
+                            Token::Word(w) => w.parse().ok(),
+                            _ => None,
+                        })
+                        .collect();
+                }
+                Token::Other(ref s) if s == "[" || s == "]" => words.push(s.clone()),
+                _ => break,
+            }
+            i += 1;
+        }
+
+        // Look for constraints following the type
+        while i < tokens.len() {
+            if is_keyword(tokens.get(i), "primary") && is_keyword(tokens.get(i + 1), "key") {
+                self.primary_key = vec![name.clone()];
+            } else if is_keyword(tokens.get(i), "unique") {
+                self.unique.push(vec![name.clone()]);
+            } else if is_keyword(tokens.get(i), "references") {
+                if let Some((table, references)) = Self::read_reference(tokens, i + 1) {
+                    self.foreign_keys
+                        .push((vec![name.clone()], table, references));
+                }
+            }
+            i += 1;
+        }
+
+        let field_name = name.parse().unwrap();
+        self.table.fields.insert(
+            field_name,
+            Field {
+                name: name.parse().unwrap(),
+                key: false,
+                cardinality: None,
+                max_length: None,
+                field_type: if words.is_empty() {
+                    None
+                } else {
+                    Some(field_type(&words, &args))
+                },
+            },
+        );
+    }
+}
+
+/// Parse SQL DDL, building tables from `CREATE TABLE` statements where
+/// unique constraints become FDs and foreign keys become INDs
+pub fn parse(input: &str) -> Result<Input, peg::error::ParseError<peg::str::LineCol>> {
+    let statements = sql::script(input)?;
+
+    let mut definitions: Vec<TableDefinition> = Vec::new();
+    let mut indexes = Vec::new();
+    for statement in statements {
+        match statement {
+            Statement::Table(name, elements) => {
+                let mut definition = TableDefinition {
+                    table: Table {
+                        name: name.parse().unwrap(),
+                        fields: IndexMap::new(),
+                        ..Default::default()
+                    },
+                    primary_key: Vec::new(),
+                    unique: Vec::new(),
+                    foreign_keys: Vec::new(),
+                };
+                for element in elements {
+                    if !definition.add_constraint(&element) {
+                        definition.add_column(&element);
+                    }
+                }
+                definitions.push(definition);
+            }
+            Statement::UniqueIndex(table, columns) => {
+                if let Some(columns) = column_names(Some(&columns)) {
+                    indexes.push((table, columns));
+                }
+            }
+        }
+    }
+
+    // Unique indexes are equivalent to unique constraints
+    for (table, columns) in indexes {
+        if let Some(definition) = definitions
+            .iter_mut()
+            .find(|d| d.table.name.as_ref() == table.as_str())
+        {
+            definition.unique.push(columns);
+        }
+    }
+
+    let primary_keys = definitions
+        .iter()
+        .map(|d| (d.table.name.to_string(), d.primary_key.clone()))
+        .collect::<HashMap<_, _>>();
+
+    let mut parsed = Input {
+        tables: Vec::new(),
+        fds: Vec::new(),
+        inds: Vec::new(),
+        stats: Vec::new(),
+    };
+    for mut definition in definitions {
+        let table_name = definition.table.name.to_string();
+        for field in definition.table.fields.values_mut() {
+            field.key = definition.primary_key.contains(&field.name.to_string());
+        }
+        definition.table.add_pk_fd();
+
+        // Each unique set of columns determines all the others
+        for columns in definition.unique {
+            let others = definition
+                .table
+                .fields
+                .keys()
+                .map(|f| f.to_string())
+                .filter(|f| !columns.contains(f))
+                .collect::<Vec<_>>();
+            if !others.is_empty() {
+                parsed.fds.push((table_name.clone(), columns, others, 1.0));
+            }
+        }
+
+        // References without columns use the primary key of the other table
+        // and a key declared both inline and as a constraint is kept once
+        for (columns, table, references) in definition.foreign_keys {
+            let references = references
+                .or_else(|| primary_keys.get(&table).cloned())
+                .unwrap_or_else(|| columns.clone());
+            let ind = (table_name.clone(), columns, table, references);
+            if !parsed.inds.contains(&ind) {
+                parsed.inds.push(ind);
+            }
+        }
+
+        parsed.tables.push(definition.table);
+    }
+
+    Ok(parsed)
+}
+
+/// The dialects of SQL which can be written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dialect {
+    PostgreSQL,
+    MySQL,
+    SQLite,
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Dialect, String> {
+        match s.to_lowercase().as_str() {
+            "postgres" | "postgresql" => Ok(Dialect::PostgreSQL),
+            "mysql" => Ok(Dialect::MySQL),
+            "sqlite" => Ok(Dialect::SQLite),
+            _ => Err(format!("unknown SQL dialect {}", s)),
+        }
+    }
+}
+
+impl Dialect {
+    /// Quote an identifier
+    fn quote(&self, name: &str) -> String {
+        match *self {
+            Dialect::MySQL => format!("`{}`", name.replace('`', "``")),
+            Dialect::PostgreSQL | Dialect::SQLite => format!("\"{}\"", name.replace('"', "\"\"")),
+        }
+    }
+
+    /// The maximum length of identifiers, if there is one
+    fn max_identifier_length(&self) -> Option<usize> {
+        match *self {
+            Dialect::MySQL => Some(64),
+            Dialect::PostgreSQL => Some(63),
+            Dialect::SQLite => None,
+        }
+    }
+
+    /// Quote a list of identifiers separated by commas
+    fn quote_all<T: AsRef<str>>(&self, names: &[T]) -> String {
+        names.iter().map(|n| self.quote(n.as_ref())).join(", ")
+    }
+
+    /// The name of the type used for a field
+    fn type_name(&self, field: &Field) -> String {
+        let length = |length: &Option<usize>| length.or(field.max_length).unwrap_or(255);
+
+        match (*self, &field.field_type) {
+            (Dialect::SQLite, Some(FieldType::Boolean))
+            | (Dialect::SQLite, Some(FieldType::SmallInt))
+            | (Dialect::SQLite, Some(FieldType::Integer))
+            | (Dialect::SQLite, Some(FieldType::BigInt)) => "INTEGER".to_string(),
+            (Dialect::SQLite, Some(FieldType::Float))
+            | (Dialect::SQLite, Some(FieldType::Double)) => "REAL".to_string(),
+            (Dialect::SQLite, Some(FieldType::Decimal(_, _))) => "NUMERIC".to_string(),
+            (Dialect::SQLite, Some(FieldType::Binary)) => "BLOB".to_string(),
+            (Dialect::SQLite, Some(FieldType::Other(name))) => name.to_uppercase(),
+            (Dialect::SQLite, _) => "TEXT".to_string(),
+
+            (_, Some(FieldType::Boolean)) => "BOOLEAN".to_string(),
+            (_, Some(FieldType::SmallInt)) => "SMALLINT".to_string(),
+            (Dialect::MySQL, Some(FieldType::Integer)) => "INT".to_string(),
+            (_, Some(FieldType::Integer)) => "INTEGER".to_string(),
+            (_, Some(FieldType::BigInt)) => "BIGINT".to_string(),
+            (Dialect::MySQL, Some(FieldType::Float)) => "FLOAT".to_string(),
+            (_, Some(FieldType::Float)) => "REAL".to_string(),
+            (Dialect::MySQL, Some(FieldType::Double)) => "DOUBLE".to_string(),
+            (_, Some(FieldType::Double)) => "DOUBLE PRECISION".to_string(),
+            (_, Some(FieldType::Decimal(precision, scale))) => {
+                let name = if *self == Dialect::MySQL {
+                    "DECIMAL"
+                } else {
+                    "NUMERIC"
+                };
+                match (precision, scale) {
+                    (Some(p), Some(s)) => format!("{}({}, {})", name, p, s),
+                    (Some(p), None) => format!("{}({})", name, p),
+                    _ => name.to_string(),
+                }
+            }
+            (_, Some(FieldType::Char(l))) => format!("CHAR({})", length(l)),
+            (_, Some(FieldType::Varchar(l))) => format!("VARCHAR({})", length(l)),
+
+            // MySQL cannot index TEXT columns without a prefix length
+            (Dialect::MySQL, Some(FieldType::Text)) if field.key => {
+                format!("VARCHAR({})", length(&None))
+            }
+            (_, Some(FieldType::Text)) => "TEXT".to_string(),
+            (Dialect::MySQL, Some(FieldType::Binary)) => "BLOB".to_string(),
+            (_, Some(FieldType::Binary)) => "BYTEA".to_string(),
+            (_, Some(FieldType::Date)) => "DATE".to_string(),
+            (_, Some(FieldType::Time)) => "TIME".to_string(),
+            (Dialect::MySQL, Some(FieldType::Timestamp)) => "DATETIME".to_string(),
+            (_, Some(FieldType::Timestamp)) => "TIMESTAMP".to_string(),
+            (Dialect::MySQL, Some(FieldType::Uuid)) => "CHAR(36)".to_string(),
+            (_, Some(FieldType::Uuid)) => "UUID".to_string(),
+            (Dialect::MySQL, Some(FieldType::Json)) => "JSON".to_string(),
+            (_, Some(FieldType::Json)) => "JSONB".to_string(),
+            (_, Some(FieldType::Other(name))) => name.to_uppercase(),
+
+            // Use a string type when nothing is known about the field
+            (Dialect::MySQL, None) => format!("VARCHAR({})", length(&None)),
+            (_, None) => "TEXT".to_string(),
+        }
+    }
+}
+
+/// Find the `IND`s in a schema which can be written as foreign keys,
+/// ordering the fields according to the key of the referenced table
+///
+/// `IND`s on missing tables are skipped and only the direction whose
+/// referencing table sorts first is kept for symmetric `IND`s.
+fn foreign_keys(schema: &Schema) -> Vec<IND> {
+    let mut keys = Vec::new();
+    let mut seen = HashSet::new();
+
+    for inds in schema.inds.values() {
+        for ind in inds {
+            let right_table = match (
+                schema.tables.get(&ind.left_table),
+                schema.tables.get(&ind.right_table),
+            ) {
+                (Some(_), Some(right_table)) => right_table,
+                _ => {
+                    warn!("Skipping {} which references a missing table", ind);
+                    continue;
+                }
+            };
+            let right_key = right_table.key_fields();
+            let right_fields = ind.right_fields.iter().cloned().collect::<HashSet<_>>();
+            if right_key.is_empty()
+                || right_key != right_fields
+                || right_key.len() != ind.right_fields.len()
+                || (ind.left_table == ind.right_table && ind.left_fields == ind.right_fields)
+            {
+                continue;
+            }
+
+            // Order fields by their position in the referenced table
+            let mut pairs = ind
+                .left_fields
+                .iter()
+                .cloned()
+                .zip(ind.right_fields.iter().cloned())
+                .collect::<Vec<_>>();
+            pairs.sort_by_key(|(_, right)| right_table.fields.get_index_of(right));
+            let (left_fields, right_fields): (Vec<_>, Vec<_>) = pairs.into_iter().unzip();
+
+            let key = IND {
+                left_table: ind.left_table.clone(),
+                left_fields,
+                right_table: ind.right_table.clone(),
+                right_fields,
+            };
+            if seen.insert(key.clone()) {
+                keys.push(key);
+            }
+        }
+    }
+
+    // Symmetric INDs would give foreign keys referencing each other
+    let pairs = |k: &IND| {
+        k.left_fields
+            .iter()
+            .cloned()
+            .zip(k.right_fields.iter().cloned())
+            .collect::<HashSet<_>>()
+    };
+    let reversed = keys
+        .iter()
+        .filter(|k| {
+            keys.iter().any(|other| {
+                other.left_table == k.right_table
+                    && other.right_table == k.left_table
+                    && (&other.left_table, &other.left_fields) < (&k.left_table, &k.left_fields)
+                    && pairs(&other.reverse()) == pairs(k)
+            })
+        })
+        .cloned()
+        .collect::<HashSet<_>>();
+    keys.retain(|k| !reversed.contains(k));
+
+    keys.sort_by_key(|k| {
+        (
+            k.left_table.clone(),
+            k.right_table.clone(),
+            k.left_fields.clone(),
+        )
+    });
+    keys
+}
+
+/// Names shortened to fit within a maximum length while staying unique
+struct ShortNames {
+    max_length: Option<usize>,
+    reserved: HashSet<String>,
+    used: HashSet<String>,
+}
+
+impl ShortNames {
+    fn new(max_length: Option<usize>) -> ShortNames {
+        ShortNames {
+            max_length,
+            reserved: HashSet::new(),
+            used: HashSet::new(),
+        }
+    }
+
+    /// Reserve names which will be used later so other names are not
+    /// shortened to the same name
+    fn reserve<'a, I: IntoIterator<Item = &'a str>>(&mut self, names: I) {
+        self.reserved
+            .extend(names.into_iter().map(|n| n.to_string()));
+    }
+
+    /// Shorten a name which is too long or already used, adding a
+    /// numeric suffix if the truncated name is also used
+    fn shorten(&mut self, name: &str) -> String {
+        let max_length = self.max_length.unwrap_or(usize::MAX);
+        if name.len() <= max_length && (self.reserved.remove(name) || !self.used.contains(name)) {
+            self.used.insert(name.to_string());
+            return name.to_string();
+        }
+
+        let mut count = 1;
+        loop {
+            let suffix = if count == 1 {
+                String::new()
+            } else {
+                format!("_{}", count)
+            };
+            let length = max_length - suffix.len();
+            let mut short = name
+                .char_indices()
+                .take_while(|(i, c)| i + c.len_utf8() <= length)
+                .map(|(_, c)| c)
+                .collect::<String>();
+            short.push_str(&suffix);
+            if !self.reserved.contains(&short) && self.used.insert(short.clone()) {
+                return short;
+            }
+            count += 1;
+        }
+    }
+}
+
+/// Shorten table names to fit within a maximum length, warning about
+/// any names which are changed
+pub fn short_table_names<'a, I: IntoIterator<Item = &'a TableName>>(
+    names: I,
+    max_length: Option<usize>,
+) -> HashMap<TableName, String> {
+    let names = names.into_iter().sorted().dedup().collect::<Vec<_>>();
+    let mut short_names = ShortNames::new(max_length);
+    short_names.reserve(names.iter().map(|t| t.as_ref()));
+    names
+        .into_iter()
+        .map(|t| {
+            let name = short_names.shorten(t.as_ref());
+            if name != t.as_ref() {
+                warn!("Shortened table name {} to {}", t, name);
+            }
+            (t.clone(), name)
+        })
+        .collect()
+}
+
+/// Write `CREATE TABLE` statements for all tables in a schema along
+/// with foreign keys for `IND`s which reference a key
+pub fn write_schema(schema: &Schema, dialect: Dialect) -> String {
+    let foreign_keys = foreign_keys(schema);
+
+    // Keep names within the identifier length limits of each dialect
+    let table_names = short_table_names(schema.tables.keys(), dialect.max_identifier_length());
+    let mut constraint_names = ShortNames::new(dialect.max_identifier_length());
+    let mut constraint_name =
+        |fk: &IND| constraint_names.shorten(&format!("fk_{}_{}", fk.left_table, fk.right_table));
+
+    let mut output = String::new();
+    let mut alters = Vec::new();
+    for table in schema.tables.values().sorted_by_key(|t| t.name.clone()) {
+        let mut lines = table
+            .fields
+            .values()
+            .map(|f| {
+                format!(
+                    "  {} {}",
+                    dialect.quote(f.name.as_ref()),
+                    dialect.type_name(f)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let key = table
+            .fields
+            .values()
+            .filter(|f| f.key)
+            .map(|f| f.name.clone())
+            .collect::<Vec<_>>();
+        if !key.is_empty() {
+            lines.push(format!("  PRIMARY KEY ({})", dialect.quote_all(&key)));
+        }
+
+        for fk in foreign_keys.iter().filter(|fk| fk.left_table == table.name) {
+            let constraint = format!(
+                "CONSTRAINT {} FOREIGN KEY ({}) REFERENCES {} ({})",
+                dialect.quote(&constraint_name(fk)),
+                dialect.quote_all(&fk.left_fields),
+                dialect.quote(&table_names[&fk.right_table]),
+                dialect.quote_all(&fk.right_fields)
+            );
+
+            // SQLite cannot add constraints later, but also does not
+            // require referenced tables to exist when they are created
+            if dialect == Dialect::SQLite {
+                lines.push(format!("  {}", constraint));
+            } else {
+                alters.push(format!(
+                    "ALTER TABLE {} ADD {};\n",
+                    dialect.quote(&table_names[&table.name]),
+                    constraint
+                ));
+            }
+        }
+
+        output.push_str(&format!(
+            "CREATE TABLE {} (\n{}\n);\n\n",
+            dialect.quote(&table_names[&table.name]),
+            lines.join(",\n")
+        ));
+    }
+
+    for alter in alters {
+        output.push_str(&alter);
+    }
+
+    output.trim_end().to_string() + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::{FieldName, TableName};
+
+    #[test]
+    fn parse_create_table() {
+        let parsed = parse(
+            "CREATE TABLE users (\n\
+               id SERIAL PRIMARY KEY,\n\
+               email VARCHAR(255) NOT NULL UNIQUE, -- login\n\
+               name character varying(20) DEFAULT 'x'\n\
+             );\n\
+             create table if not exists public.\"posts\" (\n\
+               id bigint,\n\
+               user_id int REFERENCES users,\n\
+               body text,\n\
+               price decimal(10, 2),\n\
+               CONSTRAINT posts_pk PRIMARY KEY (id),\n\
+               FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE\n\
+             ) ENGINE=InnoDB;\n\
+             INSERT INTO users VALUES (1, 'a;b', 'c');\n\
+             CREATE UNIQUE INDEX posts_body ON posts (body);\n",
+        )
+        .unwrap();
+
+        let users = &parsed.tables[0];
+        assert_eq!(users.to_string(), "users(*id, email, name)");
+        assert_eq!(
+            users.fields[&FieldName::from("email")].field_type,
+            Some(FieldType::Varchar(Some(255)))
+        );
+        assert_eq!(
+            users.fields[&FieldName::from("name")].field_type,
+            Some(FieldType::Varchar(Some(20)))
+        );
+
+        let posts = &parsed.tables[1];
+        assert_eq!(posts.to_string(), "posts(*id, body, price, user_id)");
+        assert_eq!(
+            posts.fields[&FieldName::from("price")].field_type,
+            Some(FieldType::Decimal(Some(10), Some(2)))
+        );
+
+        assert!(parsed.fds.contains(&(
+            "users".to_string(),
+            vec!["email".to_string()],
+            vec!["id".to_string(), "name".to_string()],
+            1.0
+        )));
+        assert!(parsed.fds.contains(&(
+            "posts".to_string(),
+            vec!["body".to_string()],
+            vec!["id".to_string(), "user_id".to_string(), "price".to_string()],
+            1.0
+        )));
+
+        let ind = (
+            "posts".to_string(),
+            vec!["user_id".to_string()],
+            "users".to_string(),
+            vec!["id".to_string()],
+        );
+        assert_eq!(parsed.inds, vec![ind]);
+    }
+
+    #[test]
+    fn parse_partial_index() {
+        let parsed = parse(
+            "CREATE TABLE t (a int PRIMARY KEY, b int, c int);\n\
+             CREATE UNIQUE INDEX t_b ON t (b) WHERE c > 0;\n\
+             CREATE UNIQUE INDEX t_c ON t (lower(c));",
+        )
+        .unwrap();
+        assert!(parsed.fds.is_empty());
+    }
+
+    #[test]
+    fn parse_invalid_table() {
+        assert!(parse("CREATE TABLE t a int;").is_err());
+    }
+
+    fn export_schema() -> Schema {
+        let parsed = parse(
+            "CREATE TABLE users (id int PRIMARY KEY, name text);\n\
+             CREATE TABLE posts (id int, user_id int REFERENCES users, body text,\n\
+               PRIMARY KEY (id));",
+        )
+        .unwrap();
+        parsed.into_schema()
+    }
+
+    #[test]
+    fn write_postgresql() {
+        assert_eq!(
+            write_schema(&export_schema(), Dialect::PostgreSQL),
+            "CREATE TABLE \"posts\" (\n  \"id\" INTEGER,\n  \"user_id\" INTEGER,\n  \"body\" TEXT,\n  \
+             PRIMARY KEY (\"id\")\n);\n\n\
+             CREATE TABLE \"users\" (\n  \"id\" INTEGER,\n  \"name\" TEXT,\n  PRIMARY KEY (\"id\")\n);\n\n\
+             ALTER TABLE \"posts\" ADD CONSTRAINT \"fk_posts_users\" \
+             FOREIGN KEY (\"user_id\") REFERENCES \"users\" (\"id\");\n"
+        );
+    }
+
+    #[test]
+    fn write_sqlite() {
+        let output = write_schema(&export_schema(), Dialect::SQLite);
+        assert!(output.contains(
+            "  CONSTRAINT \"fk_posts_users\" FOREIGN KEY (\"user_id\") REFERENCES \"users\" (\"id\")\n);"
+        ));
+        assert!(!output.contains("ALTER TABLE"));
+    }
+
+    #[test]
+    fn write_foreign_keys() {
+        let mut schema = export_schema();
+        add_ind!(schema, "users", ["id"], "posts", ["id"]);
+        add_ind!(schema, "posts", ["id"], "users", ["id"]);
+        add_ind!(schema, "posts", ["id"], "comments", ["id"]);
+
+        // Symmetric INDs give a single foreign key and missing tables are skipped
+        let output = write_schema(&schema, Dialect::PostgreSQL);
+        assert!(output.contains(
+            "ALTER TABLE \"posts\" ADD CONSTRAINT \"fk_posts_users\" \
+             FOREIGN KEY (\"id\") REFERENCES \"users\" (\"id\");\n"
+        ));
+        assert!(!output.contains("REFERENCES \"posts\""));
+        assert!(!output.contains("comments"));
+    }
+
+    #[test]
+    fn write_long_names() {
+        let foo = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa_foo";
+        let bar = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa_bar";
+        let mut schema = schema! {
+          table!(foo, fields! { field!("baz", true) }),
+          table!(bar, fields! { field!("baz", true) })
+        };
+        add_ind!(schema, foo, ["baz"], bar, ["baz"]);
+
+        // Shortened names are unique and used wherever a table is named
+        let output = write_schema(&schema, Dialect::PostgreSQL);
+        let short = "a".repeat(63);
+        let short_2 = format!("{}_2", "a".repeat(61));
+        assert!(output.contains(&format!("CREATE TABLE \"{}\" (", short)));
+        assert!(output.contains(&format!("CREATE TABLE \"{}\" (", short_2)));
+        assert!(output.contains(&format!(
+            "ALTER TABLE \"{}\" ADD CONSTRAINT \"fk_{}\" \
+             FOREIGN KEY (\"baz\") REFERENCES \"{}\" (\"baz\");",
+            short_2,
+            "a".repeat(60),
+            short
+        )));
+        assert!(!output.contains(&"a".repeat(64)));
+    }
+
+    #[test]
+    fn write_mysql_types() {
+        let mut table = table!(
+            "foo",
+            fields! {
+              field!("bar", true),
+              field!("baz")
+            }
+        );
+        table.fields[0].field_type = Some(FieldType::Text);
+        table.fields[1].field_type = Some(FieldType::Timestamp);
+        let schema = schema! {table};
+
+        let output = write_schema(&schema, Dialect::MySQL);
+        assert!(output.contains("`bar` VARCHAR(1)"));
+        assert!(output.contains("`baz` DATETIME"));
+    }
+}
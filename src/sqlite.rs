@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+use rusqlite::{Connection, OpenFlags};
+
+use crate::input::Input;
+use crate::model::{Field, FieldType, Table};
+use crate::sql;
+
+/// The referencing columns, referenced table, and referenced columns
+/// (if given) of a foreign key
+type ForeignKey = (Vec<String>, String, Option<Vec<String>>);
+
+/// A row count or column statistic in the form used by `Input`
+type Stat = (String, Option<String>, usize, Option<usize>);
+
+/// Quote an identifier for use in a query
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Convert a type declared on an SQLite column into a `FieldType`
+fn field_type(declared: &str) -> Option<FieldType> {
+    if declared.trim().is_empty() {
+        return None;
+    }
+
+    let (words, args) = match declared.find('(') {
+        Some(index) => (
+            &declared[..index],
+            declared[index + 1..].trim_end_matches(')'),
+        ),
+        None => (declared, ""),
+    };
+    let words = words
+        .split_whitespace()
+        .map(|w| w.to_string())
+        .collect::<Vec<_>>();
+    let args = args
+        .split(',')
+        .filter_map(|a| a.trim().parse().ok())
+        .collect::<Vec<u32>>();
+
+    Some(sql::field_type(&words, &args))
+}
+
+/// Read the names of all tables in a database, excluding internal ones
+fn table_names(conn: &Connection) -> Result<Vec<String>, rusqlite::Error> {
+    let mut statement = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+    )?;
+    let names = statement.query_map([], |row| row.get(0))?;
+    names.collect()
+}
+
+/// Read the columns of a table along with their position in the primary key
+fn read_table(conn: &Connection, name: &str) -> Result<(Table, Vec<String>), rusqlite::Error> {
+    let mut statement = conn.prepare(&format!("PRAGMA table_info({})", quote(name)))?;
+    let columns = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>("name")?,
+                row.get::<_, Option<String>>("type")?.unwrap_or_default(),
+                row.get::<_, i64>("pk")?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut table = Table {
+        name: name.parse().unwrap(),
+        fields: IndexMap::new(),
+        ..Default::default()
+    };
+    for (column, declared, _) in &columns {
+        table.fields.insert(
+            column.parse().unwrap(),
+            Field {
+                name: column.parse().unwrap(),
+                key: false,
+                cardinality: None,
+                max_length: None,
+                field_type: field_type(declared),
+            },
+        );
+    }
+
+    // Columns are numbered by their position in the primary key
+    let mut primary_key = columns.into_iter().filter(|c| c.2 > 0).collect::<Vec<_>>();
+    primary_key.sort_by_key(|c| c.2);
+    let primary_key = primary_key.into_iter().map(|c| c.0).collect::<Vec<_>>();
+
+    Ok((table, primary_key))
+}
+
+/// Read the sets of columns covered by unique indexes on a table,
+/// skipping the primary key, partial indexes and indexes on expressions
+fn unique_indexes(conn: &Connection, name: &str) -> Result<Vec<Vec<String>>, rusqlite::Error> {
+    let mut statement = conn.prepare(&format!("PRAGMA index_list({})", quote(name)))?;
+    let indexes = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>("name")?,
+                row.get::<_, bool>("unique")?,
+                row.get::<_, String>("origin")?,
+                row.get::<_, bool>("partial")?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut unique = Vec::new();
+    for (index, is_unique, origin, partial) in indexes {
+        // The primary key is already known from the columns
+        if !is_unique || origin == "pk" || partial {
+            continue;
+        }
+
+        let mut statement = conn.prepare(&format!("PRAGMA index_info({})", quote(&index)))?;
+        let columns = statement
+            .query_map([], |row| row.get::<_, Option<String>>("name"))?
+            .collect::<Result<Option<Vec<_>>, _>>()?;
+        if let Some(columns) = columns {
+            unique.push(columns);
+        }
+    }
+
+    Ok(unique)
+}
+
+/// Read the foreign keys declared on a table
+fn foreign_keys(conn: &Connection, name: &str) -> Result<Vec<ForeignKey>, rusqlite::Error> {
+    let mut statement = conn.prepare(&format!("PRAGMA foreign_key_list({})", quote(name)))?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>("id")?,
+                row.get::<_, String>("table")?,
+                row.get::<_, String>("from")?,
+                row.get::<_, Option<String>>("to")?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Each column of a composite key is listed separately
+    let mut keys: IndexMap<i64, ForeignKey> = IndexMap::new();
+    for (id, table, from, to) in rows {
+        let key = keys
+            .entry(id)
+            .or_insert_with(|| (Vec::new(), table, Some(Vec::new())));
+        key.0.push(from);
+        key.2 = match (key.2.take(), to) {
+            (Some(mut columns), Some(to)) => {
+                columns.push(to);
+                Some(columns)
+            }
+            _ => None,
+        };
+    }
+
+    Ok(keys.into_iter().map(|(_, key)| key).collect())
+}
+
+/// Count the rows of a table along with the number of distinct values
+/// and maximum length of each column, ignoring nulls
+fn table_stats(conn: &Connection, table: &Table) -> Result<Vec<Stat>, rusqlite::Error> {
+    let name = table.name.to_string();
+    let columns = table
+        .fields
+        .keys()
+        .map(|f| f.to_string())
+        .collect::<Vec<_>>();
+    let aggregates = columns
+        .iter()
+        .map(|c| format!(", COUNT(DISTINCT {0}), MAX(LENGTH({0}))", quote(c)))
+        .collect::<String>();
+    let query = format!("SELECT COUNT(*){} FROM {}", aggregates, quote(&name));
+
+    conn.query_row(&query, [], |row| {
+        let mut stats = vec![(name.clone(), None, row.get::<_, i64>(0)? as usize, None)];
+        for (i, column) in columns.iter().enumerate() {
+            let max_length = row.get::<_, Option<i64>>(2 * i + 2)?;
+            stats.push((
+                name.clone(),
+                Some(column.clone()),
+                row.get::<_, i64>(2 * i + 1)? as usize,
+                Some(max_length.unwrap_or(0) as usize),
+            ));
+        }
+        Ok(stats)
+    })
+}
+
+/// Read the tables of a database where unique indexes become FDs,
+/// foreign keys become INDs, and the data provides statistics
+fn read_database(conn: &Connection) -> Result<Input, rusqlite::Error> {
+    let mut definitions = Vec::new();
+    for name in table_names(conn)? {
+        let (table, primary_key) = read_table(conn, &name)?;
+        let unique = unique_indexes(conn, &name)?;
+        let references = foreign_keys(conn, &name)?;
+        definitions.push((table, primary_key, unique, references));
+    }
+
+    let primary_keys = definitions
+        .iter()
+        .map(|d| (d.0.name.to_string(), d.1.clone()))
+        .collect::<HashMap<_, _>>();
+
+    let mut parsed = Input {
+        tables: Vec::new(),
+        fds: Vec::new(),
+        inds: Vec::new(),
+        stats: Vec::new(),
+    };
+    for (mut table, primary_key, unique, references) in definitions {
+        let table_name = table.name.to_string();
+        for field in table.fields.values_mut() {
+            field.key = primary_key.contains(&field.name.to_string());
+        }
+        table.add_pk_fd();
+
+        // Each unique set of columns determines all the others
+        for columns in unique {
+            let others = table
+                .fields
+                .keys()
+                .map(|f| f.to_string())
+                .filter(|f| !columns.contains(f))
+                .collect::<Vec<_>>();
+            if !others.is_empty() {
+                parsed.fds.push((table_name.clone(), columns, others, 1.0));
+            }
+        }
+
+        // References without columns use the primary key of the other table
+        for (columns, other, referenced) in references {
+            let referenced = referenced
+                .or_else(|| primary_keys.get(&other).cloned())
+                .unwrap_or_else(|| columns.clone());
+            parsed
+                .inds
+                .push((table_name.clone(), columns, other, referenced));
+        }
+
+        parsed.stats.extend(table_stats(conn, &table)?);
+        parsed.tables.push(table);
+    }
+
+    Ok(parsed)
+}
+
+/// Read the schema and statistics of an SQLite database file
+pub fn read(path: &str) -> Result<Input, rusqlite::Error> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    read_database(&conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::FieldName;
+
+    #[test]
+    fn read_schema_and_stats() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, email VARCHAR(50), name TEXT);
+             CREATE UNIQUE INDEX users_email ON users (email);
+             CREATE TABLE bids (
+                 user_id INTEGER REFERENCES users,
+                 item INTEGER,
+                 amount DECIMAL(10, 2),
+                 PRIMARY KEY (item, user_id)
+             );
+             INSERT INTO users VALUES (1, 'alice@example.com', 'Alice'), (2, NULL, 'Bob');
+             INSERT INTO bids VALUES (1, 1, 5.5), (2, 1, 7), (1, 2, 3);",
+        )
+        .unwrap();
+        let parsed = read_database(&conn).unwrap();
+
+        let users = &parsed.tables[0];
+        assert_eq!(users.name.to_string(), "users");
+        assert!(users.fields[&FieldName::from("id")].key);
+        assert_eq!(
+            users.fields[&FieldName::from("email")].field_type,
+            Some(FieldType::Varchar(Some(50)))
+        );
+
+        let bids = &parsed.tables[1];
+        assert_eq!(
+            bids.fields
+                .values()
+                .filter(|f| f.key)
+                .map(|f| f.name.to_string())
+                .collect::<Vec<_>>(),
+            vec!["user_id", "item"]
+        );
+        assert_eq!(
+            bids.fields[&FieldName::from("amount")].field_type,
+            Some(FieldType::Decimal(Some(10), Some(2)))
+        );
+
+        assert_eq!(
+            parsed.fds,
+            vec![(
+                "users".to_string(),
+                vec!["email".to_string()],
+                vec!["id".to_string(), "name".to_string()],
+                1.0
+            )]
+        );
+        assert_eq!(
+            parsed.inds,
+            vec![(
+                "bids".to_string(),
+                vec!["user_id".to_string()],
+                "users".to_string(),
+                vec!["id".to_string()]
+            )]
+        );
+        assert!(parsed.stats.contains(&("users".to_string(), None, 2, None)));
+        assert!(parsed.stats.contains(&(
+            "users".to_string(),
+            Some("email".to_string()),
+            1,
+            Some(17)
+        )));
+        assert!(parsed
+            .stats
+            .contains(&("bids".to_string(), Some("item".to_string()), 2, Some(1))));
+    }
+}
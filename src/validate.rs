@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::input::Input;
+use crate::model::Table;
+
+/// How serious a problem found during validation is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found when validating an input schema
+#[derive(Debug, PartialEq)]
+pub struct Problem {
+    /// Whether this problem prevents the schema from being used
+    pub severity: Severity,
+
+    /// A description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Policies which control how problems are reported
+#[derive(Default)]
+pub struct Policy {
+    /// Treat dependencies referencing missing tables, or INDs referencing
+    /// missing fields, as warnings
+    pub ignore_missing: bool,
+
+    /// Warn about fields without the statistics used by heuristics
+    pub use_stats: bool,
+}
+
+struct Validator<'a> {
+    policy: &'a Policy,
+    tables: HashMap<&'a str, &'a Table>,
+    problems: Vec<Problem>,
+}
+
+impl<'a> Validator<'a> {
+    fn report(&mut self, severity: Severity, message: String) {
+        self.problems.push(Problem { severity, message });
+    }
+
+    /// Report a reference to a missing table or field
+    fn report_missing(&mut self, message: String) {
+        let severity = if self.policy.ignore_missing {
+            Severity::Warning
+        } else {
+            Severity::Error
+        };
+        self.report(severity, message);
+    }
+
+    /// Check that a table and all the given fields exist
+    fn check_fields(&mut self, dependency: &str, table_name: &str, fields: &[String]) {
+        let table = match self.tables.get(table_name) {
+            Some(table) => *table,
+            None => {
+                self.report_missing(format!(
+                    "{} references unknown table {}",
+                    dependency, table_name
+                ));
+                return;
+            }
+        };
+
+        for field in fields {
+            if !table.fields.contains_key(field.as_str()) {
+                self.report_missing(format!(
+                    "{} references unknown field {} on {}",
+                    dependency, field, table_name
+                ));
+            }
+        }
+    }
+}
+
+/// Check that all dependencies and statistics in the input refer to
+/// tables and fields which exist, collecting every problem found
+pub fn validate(input: &Input, policy: &Policy) -> Vec<Problem> {
+    let mut validator = Validator {
+        policy,
+        tables: HashMap::new(),
+        problems: Vec::new(),
+    };
+
+    if input.tables.is_empty() {
+        validator.report(Severity::Error, "no tables defined".to_string());
+    }
+
+    for table in &input.tables {
+        // Only the first definition of each table is used
+        if validator.tables.contains_key(table.name.as_ref()) {
+            validator.report(
+                Severity::Error,
+                format!("duplicate definition of table {}", table.name),
+            );
+        } else {
+            validator.tables.insert(table.name.as_ref(), table);
+        }
+
+        // Partition and clustering keys must be part of the primary key
+        for field in table
+            .partition_key
+            .iter()
+            .chain(table.clustering_key.iter())
+        {
+            if !table.fields.get(field).is_some_and(|f| f.key) {
+                validator.report(
+                    Severity::Error,
+                    format!(
+                        "partition or clustering field {} is not a key of {}",
+                        field, table.name
+                    ),
+                );
+            }
+        }
+
+        if table.key_fields().is_empty() {
+            validator.report(
+                Severity::Warning,
+                format!("table {} has no key fields", table.name),
+            );
+        }
+    }
+
+    for fd in &input.fds {
+        let dependency = format!("FD {} {} -> {}", fd.0, fd.1.join(", "), fd.2.join(", "));
+        if !(fd.3 > 0.0 && fd.3 <= 1.0) {
+            validator.report(
+                Severity::Error,
+                format!("{} has confidence {} outside (0, 1]", dependency, fd.3),
+            );
+        }
+
+        // Unknown fields on the LHS drop the FD while those on the RHS
+        // are dropped from the FD instead of rejecting it
+        match validator.tables.get(fd.0.as_str()).copied() {
+            Some(table) => {
+                let lhs = fd.1.iter().map(|f| (f, "the FD"));
+                let rhs = fd.2.iter().map(|f| (f, "the field"));
+                for (field, ignored) in lhs.chain(rhs) {
+                    if !table.fields.contains_key(field.as_str()) {
+                        validator.report(
+                            Severity::Warning,
+                            format!(
+                                "{} references unknown field {} on {}, so {} is ignored",
+                                dependency, field, fd.0, ignored
+                            ),
+                        );
+                    }
+                }
+            }
+            None => validator
+                .report_missing(format!("{} references unknown table {}", dependency, fd.0)),
+        }
+    }
+
+    for ind in &input.inds {
+        let dependency = format!(
+            "IND {} {} <= {} {}",
+            ind.0,
+            ind.1.join(", "),
+            ind.2,
+            ind.3.join(", ")
+        );
+        if ind.1.len() != ind.3.len() {
+            validator.report(
+                Severity::Error,
+                format!(
+                    "{} has {} fields on the left-hand side but {} on the right",
+                    dependency,
+                    ind.1.len(),
+                    ind.3.len()
+                ),
+            );
+        }
+        validator.check_fields(&dependency, &ind.0, &ind.1);
+        validator.check_fields(&dependency, &ind.2, &ind.3);
+    }
+
+    for stat in &input.stats {
+        match validator.tables.get(stat.0.as_str()) {
+            Some(table) => {
+                if let Some(ref field) = stat.1 {
+                    if !table.fields.contains_key(field.as_str()) {
+                        validator.report(
+                            Severity::Warning,
+                            format!("statistics for unknown field {} on {}", field, stat.0),
+                        );
+                    }
+                }
+            }
+            None => validator.report(
+                Severity::Warning,
+                format!("statistics for unknown table {}", stat.0),
+            ),
+        }
+    }
+
+    if policy.use_stats {
+        for table in &input.tables {
+            for field in table.fields.keys() {
+                let has_stats = input
+                    .stats
+                    .iter()
+                    .any(|s| s.0 == table.name.as_ref() && s.1.as_deref() == Some(field.as_ref()));
+                if !has_stats {
+                    validator.report(
+                        Severity::Warning,
+                        format!("no statistics for field {} on {}", field, table.name),
+                    );
+                }
+            }
+        }
+    }
+
+    validator.problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+
+    fn errors(problems: &[Problem]) -> usize {
+        problems
+            .iter()
+            .filter(|p| p.severity == Severity::Error)
+            .count()
+    }
+
+    #[test]
+    fn validate_valid() {
+        let parsed = input::parse("foo(*bar, baz)\n\nfoo bar -> baz\n").unwrap();
+        assert!(validate(&parsed, &Policy::default()).is_empty());
+    }
+
+    #[test]
+    fn validate_collects_all() {
+        let parsed = input::parse(
+            "foo(*bar, baz)\nfoo(*bar)\nquux(corge)\n\nfoo grault -> baz\n\
+             foo bar, baz <= quux corge\n\nfoo garply 1 1\n",
+        )
+        .unwrap();
+        let problems = validate(&parsed, &Policy::default());
+
+        // Duplicate table and IND arity are errors
+        assert_eq!(errors(&problems), 2);
+
+        // Keyless table, unknown fields in FDs and unknown statistics
+        // are warnings
+        assert_eq!(problems.len(), 5);
+    }
+
+    #[test]
+    fn validate_unknown_fd_field() {
+        let parsed = input::parse("foo(*bar, baz)\n\nfoo baz -> bar, quux\n").unwrap();
+        let problems = validate(&parsed, &Policy::default());
+        assert_eq!(problems.len(), 1);
+        assert_eq!(errors(&problems), 0);
+
+        // The FD is kept without the unknown field
+        let schema = parsed.into_schema();
+        let table = &schema.tables["foo"];
+        assert!(table.fds.values().any(|fd| fd.lhs.len() == 1
+            && fd.lhs.iter().all(|f| f.as_ref() == "baz")
+            && fd.rhs.iter().any(|f| f.as_ref() == "bar")));
+    }
+
+    #[test]
+    fn validate_unknown_fd_lhs_field() {
+        let parsed = input::parse("foo(*bar, baz)\n\nfoo baz, quux -> bar\n").unwrap();
+        let problems = validate(&parsed, &Policy::default());
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.ends_with("so the FD is ignored"));
+
+        // The FD is dropped entirely
+        let schema = parsed.into_schema();
+        let table = &schema.tables["foo"];
+        assert!(!table
+            .fds
+            .values()
+            .any(|fd| fd.lhs.iter().any(|f| f.as_ref() == "baz")));
+    }
+
+    #[test]
+    fn validate_ignore_missing() {
+        let parsed = input::parse("foo(*bar, baz)\n\nquux bar -> baz\n").unwrap();
+        let policy = Policy {
+            ignore_missing: true,
+            ..Default::default()
+        };
+        let problems = validate(&parsed, &policy);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(errors(&problems), 0);
+    }
+}